@@ -0,0 +1,86 @@
+use alloy_flashblocks::{decode_flashblock, Flashblock, FlashblockRef};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde_json::{json, Map, Value};
+
+/// A busy flashblock: `transactions` raw transactions with a receipt and two
+/// logs each, and a balance update for every sender and recipient.
+fn message(transactions: usize) -> String {
+    let hex = |seed: usize, bytes: usize| {
+        let digits: String = (0..bytes * 2)
+            .map(|i| char::from(b"0123456789abcdef"[(seed * 7 + i) % 16]))
+            .collect();
+        format!("0x{}", digits)
+    };
+
+    let mut receipts = Map::new();
+    let mut balances = Map::new();
+    for i in 0..transactions {
+        let log = json!({
+            "address": hex(i, 20),
+            "topics": [hex(i + 1, 32), hex(i + 2, 32), hex(i + 3, 32)],
+            "data": hex(i + 4, 32),
+        });
+        receipts.insert(
+            hex(i, 32),
+            json!({
+                "Eip1559": {
+                    "status": "0x1",
+                    "cumulativeGasUsed": format!("{:#x}", 21_000 * (i + 1)),
+                    "logs": [log.clone(), log],
+                }
+            }),
+        );
+        balances.insert(hex(i + 5, 20), Value::String(format!("{:#x}", i * 1_000)));
+        balances.insert(hex(i + 6, 20), Value::String(format!("{:#x}", i * 2_000)));
+    }
+
+    json!({
+        "payload_id": "0x0316ecb1aa1671b5",
+        "index": 3,
+        "diff": {
+            "state_root": hex(1, 32),
+            "block_hash": hex(2, 32),
+            "gas_used": "0x1c9c380",
+            "transactions": (0..transactions).map(|i| hex(i, 180)).collect::<Vec<_>>(),
+            "withdrawals": [],
+        },
+        "metadata": {
+            "block_number": 25_000_000,
+            "new_account_balances": balances,
+            "receipts": receipts,
+        }
+    })
+    .to_string()
+}
+
+fn decode(c: &mut Criterion) {
+    let message = message(100);
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Bytes(message.len() as u64));
+
+    // The previous path: parse into a Value, then clone it into a Flashblock.
+    group.bench_function("value_then_clone", |b| {
+        b.iter(|| {
+            let json: Value = serde_json::from_str(black_box(&message)).unwrap();
+            assert!(json.get("error").is_none());
+            serde_json::from_value::<Flashblock>(json.clone()).unwrap()
+        })
+    });
+
+    group.bench_function("direct", |b| {
+        b.iter_batched_ref(
+            || message.clone().into_bytes(),
+            |buf| decode_flashblock(buf).unwrap().unwrap(),
+            criterion::BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("borrowed", |b| {
+        b.iter(|| FlashblockRef::parse(black_box(&message)).unwrap().index)
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
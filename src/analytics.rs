@@ -0,0 +1,212 @@
+use crate::{
+    server::decode_transaction,
+    types::{Flashblock, FlashblockBase},
+};
+use alloy::{consensus::Transaction, eips::eip1559::BaseFeeParams};
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+/// Gas and fee figures of a single flashblock.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlashblockGas {
+    pub block_number: Option<u64>,
+    pub payload_id: String,
+    pub index: u64,
+    pub base_fee_per_gas: Option<u128>,
+    pub gas_limit: Option<u64>,
+    /// Cumulative gas used by the block up to and including this flashblock
+    pub gas_used: u64,
+    /// Gas consumed by this flashblock alone
+    pub gas_delta: u64,
+    /// Cumulative gas used as a fraction of the gas limit
+    pub fill_rate: Option<f64>,
+    pub transactions: usize,
+    pub priority_fee_min: Option<u128>,
+    pub priority_fee_median: Option<u128>,
+    pub priority_fee_max: Option<u128>,
+    /// Base fee of the next block if this block were sealed now
+    pub predicted_next_base_fee: Option<u64>,
+}
+
+/// Sorted priority fees paid by the signed transactions of a flashblock at
+/// the given base fee.
+pub fn priority_fees(flashblock: &Flashblock, base_fee: u128) -> Vec<u128> {
+    let Ok(base_fee) = u64::try_from(base_fee) else {
+        return Vec::new();
+    };
+    let mut fees: Vec<u128> = flashblock
+        .diff
+        .transactions
+        .iter()
+        .flatten()
+        .filter_map(|raw| decode_transaction(raw))
+        .filter_map(|tx| tx.effective_tip_per_gas(base_fee))
+        .collect();
+    fees.sort_unstable();
+    fees
+}
+
+/// Derives gas usage and base-fee predictions from consecutive flashblocks.
+#[derive(Debug, Clone)]
+pub struct GasAnalytics {
+    params: BaseFeeParams,
+    payload_id: Option<String>,
+    base: Option<FlashblockBase>,
+    gas_used: u64,
+}
+
+impl Default for GasAnalytics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GasAnalytics {
+    /// Uses the OP Stack post-Canyon parameters (denominator 250,
+    /// elasticity 6).
+    pub fn new() -> Self {
+        Self {
+            params: BaseFeeParams::optimism_canyon(),
+            payload_id: None,
+            base: None,
+            gas_used: 0,
+        }
+    }
+
+    pub fn with_base_fee_params(mut self, params: BaseFeeParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn observe(&mut self, flashblock: &Flashblock) -> FlashblockGas {
+        if self.payload_id.as_deref() != Some(flashblock.payload_id.as_str()) {
+            self.payload_id = Some(flashblock.payload_id.clone());
+            self.base = None;
+            self.gas_used = 0;
+        }
+        if let Some(base) = &flashblock.base {
+            self.base = Some(base.clone());
+        }
+
+        let gas_used = flashblock.diff.gas_used().unwrap_or(self.gas_used);
+        let gas_delta = gas_used.saturating_sub(self.gas_used);
+        self.gas_used = gas_used;
+
+        let base_fee = self.base.as_ref().and_then(|base| base.base_fee_per_gas());
+        let gas_limit = self.base.as_ref().and_then(|base| base.gas_limit());
+
+        let priority_fees = base_fee
+            .map(|base_fee| priority_fees(flashblock, base_fee))
+            .unwrap_or_default();
+
+        FlashblockGas {
+            block_number: flashblock.block_number(),
+            payload_id: flashblock.payload_id.clone(),
+            index: flashblock.index,
+            base_fee_per_gas: base_fee,
+            gas_limit,
+            gas_used,
+            gas_delta,
+            fill_rate: gas_limit
+                .filter(|limit| *limit > 0)
+                .map(|limit| gas_used as f64 / limit as f64),
+            transactions: flashblock.transaction_count(),
+            priority_fee_min: priority_fees.first().copied(),
+            priority_fee_median: priority_fees.get(priority_fees.len() / 2).copied(),
+            priority_fee_max: priority_fees.last().copied(),
+            predicted_next_base_fee: base_fee
+                .and_then(|base_fee| u64::try_from(base_fee).ok())
+                .zip(gas_limit)
+                .map(|(base_fee, gas_limit)| {
+                    self.params
+                        .next_block_base_fee(gas_used, gas_limit, base_fee)
+                }),
+        }
+    }
+
+    /// Consumes flashblocks and sends the gas figures of each.
+    pub async fn run(
+        mut self,
+        mut flashblocks: mpsc::UnboundedReceiver<Flashblock>,
+        stats: mpsc::UnboundedSender<FlashblockGas>,
+    ) {
+        while let Some(flashblock) = flashblocks.recv().await {
+            if stats.send(self.observe(&flashblock)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FlashblockBuilder;
+
+    const BASE_FEE: u64 = FlashblockBuilder::BASE_FEE as u64;
+    const GAS_LIMIT: u64 = FlashblockBuilder::GAS_LIMIT;
+
+    fn flashblock(payload_id: &str, index: u64, gas_used: u64) -> Flashblock {
+        let builder = Flashblock::builder(payload_id, index).with_gas_used(gas_used);
+        if index == 0 {
+            builder.with_base().build()
+        } else {
+            builder.build()
+        }
+    }
+
+    #[test]
+    fn tracks_gas_across_flashblocks_of_a_payload() {
+        let mut analytics = GasAnalytics::new();
+        let first = analytics.observe(&flashblock("0x01", 0, 1_000_000));
+        let second = analytics.observe(&flashblock("0x01", 1, 4_000_000));
+
+        assert_eq!(first.gas_delta, 1_000_000);
+        assert_eq!(second.gas_delta, 3_000_000);
+        // The base of the first flashblock applies to the whole payload.
+        assert_eq!(second.base_fee_per_gas, Some(BASE_FEE as u128));
+        assert_eq!(second.fill_rate, Some(4_000_000.0 / GAS_LIMIT as f64));
+
+        let next = analytics.observe(&flashblock("0x02", 0, 500_000));
+        assert_eq!(next.gas_delta, 500_000);
+    }
+
+    #[test]
+    fn predicts_next_base_fee() {
+        let params = BaseFeeParams::optimism_canyon();
+        let target = GAS_LIMIT / params.elasticity_multiplier as u64;
+        let predict = |gas_used| {
+            GasAnalytics::new()
+                .observe(&flashblock("0x01", 0, gas_used))
+                .predicted_next_base_fee
+                .unwrap()
+        };
+
+        assert_eq!(predict(target), BASE_FEE);
+        assert!(predict(target + 1) > BASE_FEE);
+        assert!(predict(0) < BASE_FEE);
+        // A full block raises the base fee by (elasticity - 1) / denominator.
+        let increase =
+            BASE_FEE as u128 * (params.elasticity_multiplier - 1) / params.max_change_denominator;
+        assert_eq!(predict(GAS_LIMIT), BASE_FEE + increase as u64);
+    }
+
+    #[test]
+    fn no_prediction_without_base() {
+        let gas = GasAnalytics::new().observe(&flashblock("0x01", 1, 1_000));
+        assert_eq!(gas.base_fee_per_gas, None);
+        assert_eq!(gas.predicted_next_base_fee, None);
+        assert_eq!(gas.priority_fee_median, None);
+    }
+
+    #[test]
+    fn no_prediction_for_base_fees_beyond_u64() {
+        let gas = GasAnalytics::new().observe(
+            &Flashblock::builder("0x01", 0)
+                .with_base_fee(u64::MAX as u128 + 1)
+                .build(),
+        );
+        assert_eq!(gas.base_fee_per_gas, Some(u64::MAX as u128 + 1));
+        assert_eq!(gas.predicted_next_base_fee, None);
+    }
+}
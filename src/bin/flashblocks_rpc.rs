@@ -0,0 +1,7 @@
+use alloy_flashblocks::Cli;
+use std::error::Error;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    Cli::parse_with_subcommand("serve").run().await
+}
@@ -1,23 +1,1012 @@
-use clap::{Parser, Subcommand};
+#[cfg(feature = "parquet")]
+use crate::export::ParquetExporter;
+#[cfg(feature = "sqlite")]
+use crate::storage::SqliteStore;
+use crate::{
+    analytics::GasAnalytics,
+    config::{Config, Profile, Settings},
+    fees::FeeEstimator,
+    logs::LogSubscription,
+    monitor::FlashblocksMonitor,
+    network::{self, Network},
+    output::{BalanceRecord, FlashblockRecord, OutputFormat, OutputWriter, ReceiptRecord},
+    reconcile::Reconciler,
+    rpc::FlashblocksRpcClient,
+    server::PreconfServer,
+    state::PendingState,
+    submit::{self, TransferRequest},
+    transfers::TransferTracker,
+    watch::Watchlist,
+    websocket::{FlashblocksWsClient, Subscription},
+};
+use alloy::{
+    primitives::{Address, B256, U256},
+    rpc::types::Filter,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::{
+    error::Error, ffi::OsString, fmt::Display, fs::OpenOptions, net::SocketAddr, path::PathBuf,
+    str::FromStr, sync::Mutex, time::Duration,
+};
+use tokio::{sync::mpsc, time};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+use url::Url;
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "Global options can also be set with FLASHBLOCKS_<OPTION> environment \
+                  variables, e.g. FLASHBLOCKS_WS_URL or FLASHBLOCKS_RECONNECT_ATTEMPTS."
+)]
 pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
+#[derive(Args, Clone)]
+pub struct GlobalArgs {
+    /// Path to a TOML configuration file
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Configuration profile (built-in: base-mainnet, base-sepolia, local)
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Network preset providing endpoints and the expected chain id
+    #[arg(long, global = true, value_enum)]
+    pub network: Option<Network>,
+
+    /// Skip the startup check of chain id and endpoint consistency
+    #[arg(long, global = true, env = "FLASHBLOCKS_SKIP_NETWORK_CHECK")]
+    pub skip_network_check: bool,
+
+    /// Flashblocks WebSocket URL
+    #[arg(long, global = true)]
+    pub ws_url: Option<Url>,
+
+    /// JSON-RPC URL (a preconfirmation-aware endpoint)
+    #[arg(long, global = true)]
+    pub rpc_url: Option<Url>,
+
+    /// Timeout in seconds for connections and requests [default: 30]
+    #[arg(long, global = true)]
+    pub timeout: Option<u64>,
+
+    /// Chain id used when signing transactions
+    #[arg(long, global = true)]
+    pub chain_id: Option<u64>,
+
+    /// Output format for command results (logs are written to stderr) [default: text]
+    #[arg(long, global = true, value_enum)]
+    pub output: Option<OutputFormat>,
+
+    /// Port to serve Prometheus metrics on
+    #[arg(long, global = true)]
+    pub metrics_port: Option<u16>,
+
+    /// Delay between WebSocket reconnection attempts in milliseconds
+    #[arg(long, global = true)]
+    pub reconnect_delay_ms: Option<u64>,
+
+    /// Maximum consecutive WebSocket connection attempts
+    #[arg(long, global = true)]
+    pub reconnect_attempts: Option<u32>,
+
+    /// Interval between WebSocket pings in milliseconds
+    #[arg(long, global = true)]
+    pub ping_interval_ms: Option<u64>,
+
+    /// Reconnect when no flashblock arrives for this many milliseconds
+    #[arg(long, global = true)]
+    pub idle_timeout_ms: Option<u64>,
+
+    /// Subscription handshake: none, eth_subscribe[:<name>] or <method>[:<params JSON>]
+    #[arg(long, global = true)]
+    pub subscription: Option<Subscription>,
+}
+
+impl GlobalArgs {
+    /// Resolves settings with the precedence: CLI flags, `FLASHBLOCKS_*`
+    /// environment variables, the `--network` preset, configuration file,
+    /// built-in profile.
+    pub fn resolve(&self) -> eyre::Result<Settings> {
+        self.resolve_with_env(|name| std::env::var(name).ok())
+    }
+
+    /// Like [`Self::resolve`], reading `FLASHBLOCKS_*` variables through
+    /// `env` instead of the process environment.
+    pub fn resolve_with_env(&self, env: impl Fn(&str) -> Option<String>) -> eyre::Result<Settings> {
+        let env = Env(env);
+        let config = self.config.clone().or(env.parse("FLASHBLOCKS_CONFIG")?);
+        let profile_name = self.profile.clone().or(env.parse("FLASHBLOCKS_PROFILE")?);
+        let network = self.network.or(env.value_enum("FLASHBLOCKS_NETWORK")?);
+
+        let config = Config::load(config.as_deref())?;
+        let mut profile = config.profile(profile_name.as_deref())?;
+        if let Some(network) = network {
+            profile = profile.merge(Profile::from_network(network));
+        }
+        let mut settings = Settings::from_profile(Profile {
+            network: network.or(profile.network),
+            ws_url: self
+                .ws_url
+                .clone()
+                .or(env.parse("FLASHBLOCKS_WS_URL")?)
+                .or(profile.ws_url),
+            rpc_url: self
+                .rpc_url
+                .clone()
+                .or(env.parse("FLASHBLOCKS_RPC_URL")?)
+                .or(profile.rpc_url),
+            upstream_url: profile.upstream_url,
+            chain_id: self
+                .chain_id
+                .or(env.parse("FLASHBLOCKS_CHAIN_ID")?)
+                .or(profile.chain_id),
+            timeout: self
+                .timeout
+                .or(env.parse("FLASHBLOCKS_TIMEOUT")?)
+                .or(profile.timeout),
+            output: self
+                .output
+                .or(env.value_enum("FLASHBLOCKS_OUTPUT")?)
+                .or(profile.output),
+            metrics_port: self
+                .metrics_port
+                .or(env.parse("FLASHBLOCKS_METRICS_PORT")?)
+                .or(profile.metrics_port),
+            reconnect: profile.reconnect,
+            subscription: self
+                .subscription
+                .clone()
+                .or(env.parse("FLASHBLOCKS_SUBSCRIPTION")?)
+                .or(profile.subscription),
+        })?;
+
+        let reconnect_delay_ms = self
+            .reconnect_delay_ms
+            .or(env.parse("FLASHBLOCKS_RECONNECT_DELAY_MS")?);
+        if let Some(delay_ms) = reconnect_delay_ms {
+            settings.reconnect.delay_ms = delay_ms;
+        }
+        let reconnect_attempts = self
+            .reconnect_attempts
+            .or(env.parse("FLASHBLOCKS_RECONNECT_ATTEMPTS")?);
+        if let Some(max_attempts) = reconnect_attempts {
+            settings.reconnect.max_attempts = max_attempts;
+        }
+        let ping_interval_ms = self
+            .ping_interval_ms
+            .or(env.parse("FLASHBLOCKS_PING_INTERVAL_MS")?);
+        if let Some(ping_interval_ms) = ping_interval_ms {
+            settings.reconnect.ping_interval_ms = ping_interval_ms;
+        }
+        let idle_timeout_ms = self
+            .idle_timeout_ms
+            .or(env.parse("FLASHBLOCKS_IDLE_TIMEOUT_MS")?);
+        if let Some(idle_timeout_ms) = idle_timeout_ms {
+            settings.reconnect.idle_timeout_ms = idle_timeout_ms;
+        }
+        Ok(settings)
+    }
+}
+
+/// Reads and parses `FLASHBLOCKS_*` variables for [`GlobalArgs`]. Empty
+/// variables count as unset.
+struct Env<F>(F);
+
+impl<F: Fn(&str) -> Option<String>> Env<F> {
+    fn get(&self, name: &str) -> Option<String> {
+        (self.0)(name).filter(|value| !value.is_empty())
+    }
+
+    fn parse<T: FromStr>(&self, name: &str) -> eyre::Result<Option<T>>
+    where
+        T::Err: Display,
+    {
+        self.get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|e| eyre::eyre!("Invalid {}={:?}: {}", name, value, e))
+            })
+            .transpose()
+    }
+
+    fn value_enum<T: ValueEnum>(&self, name: &str) -> eyre::Result<Option<T>> {
+        self.get(name)
+            .map(|value| {
+                T::from_str(&value, true)
+                    .map_err(|e| eyre::eyre!("Invalid {}={:?}: {}", name, value, e))
+            })
+            .transpose()
+    }
+}
+
+impl Settings {
+    pub fn rpc_client(&self) -> FlashblocksRpcClient {
+        FlashblocksRpcClient::new(self.rpc_url.to_string()).with_timeout(self.timeout)
+    }
+
+    pub fn ws_client(&self, max_blocks: usize) -> FlashblocksWsClient {
+        FlashblocksWsClient::new(self.ws_url.clone(), max_blocks)
+            .with_connect_timeout(self.timeout)
+            .with_reconnect_delay(self.reconnect.delay())
+            .with_max_attempts(self.reconnect.max_attempts)
+            .with_ping_interval(self.reconnect.ping_interval())
+            .with_idle_timeout(self.reconnect.idle_timeout())
+            .with_subscription(self.subscription.clone())
+    }
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
+    /// Stream flashblocks from the WebSocket feed
+    Ws {
+        /// Number of blocks to stream
+        #[arg(short, long, default_value_t = 5)]
+        blocks: usize,
+    },
+    /// Report gas usage, priority fees and the predicted next base fee per flashblock
+    Gas {
+        /// Number of blocks to stream
+        #[arg(short, long, default_value_t = 5)]
+        blocks: usize,
+    },
+    /// Poll the pending block over JSON-RPC
     Stream {
+        /// Number of blocks to stream
+        #[arg(short, long, default_value_t = 5)]
+        blocks: u64,
+    },
+    /// Monitor flashblocks with periodic statistics
+    Monitor {
+        /// Address to serve Prometheus metrics on (e.g. 0.0.0.0:9100)
+        #[arg(long)]
+        metrics_addr: Option<SocketAddr>,
+        /// Show an interactive terminal dashboard instead of log output
+        #[arg(long)]
+        tui: bool,
+        /// File that receives log output while the dashboard is shown
+        #[arg(long, default_value = "flashblocks-monitor.log")]
+        log_file: PathBuf,
+        /// Warn when the local clock is off from block timestamps by more
+        /// than this many milliseconds
+        #[arg(long, default_value_t = 1000)]
+        skew_threshold_ms: u64,
+    },
+    /// Serve preconfirmed state from the WebSocket feed over JSON-RPC
+    Serve {
+        /// Upstream RPC URL for requests that cannot be answered locally
+        #[arg(long, env = "FLASHBLOCKS_UPSTREAM_URL")]
+        upstream: Option<Url>,
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8545")]
+        listen: SocketAddr,
+    },
+    /// Compare assembled flashblocks with the sealed canonical blocks
+    Reconcile {
+        /// Number of blocks to reconcile
         #[arg(short, long, default_value_t = 5)]
         blocks: usize,
+        /// Only output reorg and dropped payload events
+        #[arg(long)]
+        events: bool,
+        /// Verify the transactions and receipts roots against the canonical header
+        #[arg(long)]
+        verify_roots: bool,
+    },
+    /// Report balance changes of addresses as flashblocks arrive
+    Watch {
+        /// Addresses to watch
+        #[arg(required = true)]
+        addresses: Vec<Address>,
+    },
+    /// Stream logs from preconfirmed receipts matching a filter
+    Logs {
+        /// Contract addresses to match (any of)
+        #[arg(long)]
+        address: Vec<Address>,
+        /// Event signature for topic0, e.g. "Transfer(address,address,uint256)"
+        #[arg(long)]
+        event: Option<String>,
+        /// Values to match for topic1 (any of)
+        #[arg(long)]
+        topic1: Vec<B256>,
+        /// Values to match for topic2 (any of)
+        #[arg(long)]
+        topic2: Vec<B256>,
+        /// Values to match for topic3 (any of)
+        #[arg(long)]
+        topic3: Vec<B256>,
+    },
+    /// Stream preconfirmed ERC-20 transfers
+    Transfers {
+        /// Token contracts to track (default: all)
+        #[arg(long)]
+        token: Vec<Address>,
+        /// Only show transfers from or to these wallets
+        #[arg(long)]
+        wallet: Vec<Address>,
     },
+    /// Record flashblocks to a SQLite database
+    #[cfg(feature = "sqlite")]
+    Record {
+        /// Number of blocks to record (default: until interrupted)
+        #[arg(short, long)]
+        blocks: Option<usize>,
+        /// Database file
+        #[arg(long, default_value = "flashblocks.db", env = "FLASHBLOCKS_DB")]
+        db: PathBuf,
+    },
+    /// Query flashblocks recorded with `record`
+    #[cfg(feature = "sqlite")]
+    History {
+        /// Database file
+        #[arg(long, default_value = "flashblocks.db", env = "FLASHBLOCKS_DB")]
+        db: PathBuf,
+        #[command(subcommand)]
+        query: HistoryQuery,
+    },
+    /// Export flashblocks to hourly partitioned Parquet tables
+    #[cfg(feature = "parquet")]
+    Export {
+        /// Output directory
+        #[arg(long, default_value = "flashblocks-parquet")]
+        out: PathBuf,
+        /// Capture of raw flashblock messages (one JSON object per line) to
+        /// export instead of the live feed
+        #[arg(long)]
+        input: Option<PathBuf>,
+        /// Number of blocks to export from the live feed (default: until interrupted)
+        #[arg(short, long)]
+        blocks: Option<usize>,
+    },
+    /// Query the latest Flashblock
     QueryLatest,
+    /// Get balance for an address
     GetBalance {
+        /// Address to query
         address: String,
     },
+    /// Get the receipt for a transaction
     GetReceipt {
+        /// Transaction hash
         tx_hash: String,
     },
+    /// Submit a transfer and wait for its preconfirmation
+    ///
+    /// Without a private key, only probes the pending-state RPC methods.
+    Submit {
+        /// Private key used to sign the transaction
+        #[arg(long)]
+        private_key: Option<String>,
+        /// Recipient (defaults to the sender)
+        #[arg(long)]
+        to: Option<Address>,
+        /// Value to transfer in wei
+        #[arg(long, default_value_t = U256::ZERO)]
+        value: U256,
+        /// Price the transaction for inclusion within this many flashblocks
+        #[arg(long, default_value_t = 3)]
+        target_flashblocks: u64,
+        /// Let the RPC node estimate fees instead of the flashblock feed
+        #[arg(long)]
+        node_fees: bool,
+        /// Lowest priority fee in wei to pay when estimating from the feed
+        #[arg(long, default_value_t = 1)]
+        priority_fee_floor: u128,
+    },
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(Subcommand)]
+pub enum HistoryQuery {
+    /// When and at which index a transaction was first preconfirmed
+    Tx {
+        /// Transaction hash
+        hash: String,
+    },
+    /// Timeline of every flashblock recorded for a block
+    Block {
+        /// Block number
+        number: u64,
+    },
+}
+
+impl Cli {
+    /// Parses the process arguments as if `subcommand` had been given first,
+    /// so standalone binaries can expose a single command of this CLI.
+    pub fn parse_with_subcommand(subcommand: &str) -> Self {
+        let mut args: Vec<OsString> = std::env::args_os().collect();
+        args.insert(1.min(args.len()), subcommand.into());
+        Self::parse_from(args)
+    }
+
+    /// Whether a feature-gated command streams from the WebSocket feed.
+    fn records(command: &Commands) -> bool {
+        match command {
+            #[cfg(feature = "sqlite")]
+            Commands::Record { .. } => true,
+            #[cfg(feature = "parquet")]
+            Commands::Export { input, .. } => input.is_none(),
+            _ => false,
+        }
+    }
+
+    pub async fn run(self) -> Result<(), Box<dyn Error>> {
+        match &self.command {
+            // The dashboard owns the terminal, so logs go to a file.
+            Commands::Monitor {
+                tui: true,
+                log_file,
+                ..
+            } => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(log_file)
+                    .map_err(|e| format!("Failed to open {}: {}", log_file.display(), e))?;
+                tracing_subscriber::fmt()
+                    .with_writer(Mutex::new(file))
+                    .with_ansi(false)
+                    .init();
+            }
+            _ => tracing_subscriber::fmt()
+                .with_writer(std::io::stderr)
+                .init(),
+        }
+
+        let settings = self.global.resolve()?;
+
+        let streams = matches!(
+            self.command,
+            Commands::Ws { .. }
+                | Commands::Gas { .. }
+                | Commands::Monitor { .. }
+                | Commands::Serve { .. }
+                | Commands::Reconcile { .. }
+                | Commands::Watch { .. }
+                | Commands::Logs { .. }
+                | Commands::Transfers { .. }
+        ) || Self::records(&self.command);
+        if streams && !self.global.skip_network_check {
+            network::validate(&settings).await?;
+        }
+
+        let unbounded = streams || matches!(self.command, Commands::Stream { .. });
+        let shutdown = CancellationToken::new();
+        if unbounded {
+            tokio::spawn(cancel_on_signal(shutdown.clone()));
+        }
+
+        // A JSON document can only be written once the command completes,
+        // which for a stream means buffering everything until exit.
+        let format = match settings.output {
+            OutputFormat::Json if unbounded => {
+                warn!("JSON output is not available for streaming commands; writing ndjson");
+                OutputFormat::Ndjson
+            }
+            format => format,
+        };
+        let mut out = OutputWriter::stdout(format);
+
+        match self.command {
+            Commands::Ws { blocks } => {
+                let (tx, mut rx) = mpsc::unbounded_channel();
+                let client = settings
+                    .ws_client(blocks)
+                    .with_cancellation(shutdown.clone())
+                    .with_sender(tx);
+                let stream = tokio::spawn(async move { client.stream_blocks().await });
+
+                let mut received = 0;
+                while let Some(flashblock) = rx.recv().await {
+                    out.write(&FlashblockRecord::from(&flashblock))?;
+                    received += 1;
+                }
+                stream.await??;
+                info!("Received {} flashblocks", received);
+            }
+            Commands::Gas { blocks } => {
+                let (flashblocks_tx, flashblocks_rx) = mpsc::unbounded_channel();
+                let (stats_tx, mut stats_rx) = mpsc::unbounded_channel();
+                let client = settings
+                    .ws_client(blocks)
+                    .with_cancellation(shutdown.clone())
+                    .with_sender(flashblocks_tx);
+                let stream = tokio::spawn(async move { client.stream_blocks().await });
+                tokio::spawn(GasAnalytics::new().run(flashblocks_rx, stats_tx));
+
+                while let Some(stats) = stats_rx.recv().await {
+                    out.write(&stats)?;
+                }
+                stream.await??;
+            }
+            Commands::Stream { blocks } => {
+                settings
+                    .rpc_client()
+                    .with_cancellation(shutdown.clone())
+                    .monitor_blocks_with(blocks, |block| Ok(out.write(&block)?))
+                    .await?;
+            }
+            Commands::Monitor {
+                metrics_addr,
+                tui,
+                skew_threshold_ms,
+                ..
+            } => {
+                let metrics_addr = metrics_addr.or_else(|| {
+                    settings
+                        .metrics_port
+                        .map(|port| SocketAddr::from(([0, 0, 0, 0], port)))
+                });
+                FlashblocksMonitor::new(settings.ws_client(usize::MAX))
+                    .with_metrics_addr(metrics_addr)
+                    .with_tui(tui)
+                    .with_skew_threshold(Duration::from_millis(skew_threshold_ms))
+                    .with_cancellation(shutdown.clone())
+                    .run()
+                    .await?;
+            }
+            Commands::Serve { upstream, listen } => {
+                let upstream = upstream.unwrap_or_else(|| settings.upstream_url.clone());
+                serve(&settings, upstream, listen, shutdown).await?;
+            }
+            Commands::Reconcile {
+                blocks,
+                events,
+                verify_roots,
+            } => {
+                let canonical = FlashblocksRpcClient::new(settings.upstream_url.to_string())
+                    .with_timeout(settings.timeout);
+                let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+                let reconciler = Reconciler::new(canonical)
+                    .with_root_verification(verify_roots)
+                    .with_event_sender(events_tx);
+
+                let (flashblocks_tx, flashblocks_rx) = mpsc::unbounded_channel();
+                let (reports_tx, mut reports_rx) = mpsc::unbounded_channel();
+                // Stops only this stream once enough blocks are reconciled.
+                let cancel = shutdown.child_token();
+                let client = settings
+                    .ws_client(usize::MAX)
+                    .with_cancellation(cancel.clone())
+                    .with_sender(flashblocks_tx);
+                let stream = tokio::spawn(async move { client.stream_blocks().await });
+                tokio::spawn(reconciler.run(flashblocks_rx, reports_tx));
+
+                let mut reconciled = 0;
+                while reconciled < blocks {
+                    let Some(report) = reports_rx.recv().await else {
+                        break;
+                    };
+                    // Events are sent before their report, so they are
+                    // already queued by the time the report arrives.
+                    while let Ok(event) = events_rx.try_recv() {
+                        if events {
+                            out.write(&event)?;
+                        }
+                    }
+                    if !events {
+                        out.write(&report)?;
+                    }
+                    reconciled += 1;
+                }
+                drop(reports_rx);
+                cancel.cancel();
+                stream.await??;
+            }
+            Commands::Watch { addresses } => {
+                let mut watchlist = Watchlist::new(addresses.iter().copied());
+                let rpc = settings.rpc_client();
+                for address in &addresses {
+                    match rpc.get_balance_at(&address.to_string(), "pending").await {
+                        Ok(balance) => watchlist.seed(*address, balance),
+                        Err(e) => warn!("Failed to fetch balance of {}: {}", address, e),
+                    }
+                }
+
+                let (flashblocks_tx, flashblocks_rx) = mpsc::unbounded_channel();
+                let (changes_tx, mut changes_rx) = mpsc::unbounded_channel();
+                let client = settings
+                    .ws_client(usize::MAX)
+                    .with_cancellation(shutdown.clone())
+                    .with_sender(flashblocks_tx);
+                let stream = tokio::spawn(async move { client.stream_blocks().await });
+                tokio::spawn(watchlist.run(flashblocks_rx, changes_tx));
+
+                while let Some(change) = changes_rx.recv().await {
+                    out.write(&change)?;
+                }
+                stream.await??;
+            }
+            Commands::Logs {
+                address,
+                event,
+                topic1,
+                topic2,
+                topic3,
+            } => {
+                let mut filter = Filter::new()
+                    .address(address)
+                    .topic1(topic1)
+                    .topic2(topic2)
+                    .topic3(topic3);
+                if let Some(event) = &event {
+                    filter = filter.event(event);
+                }
+
+                let (flashblocks_tx, flashblocks_rx) = mpsc::unbounded_channel();
+                let (logs_tx, mut logs_rx) = mpsc::unbounded_channel();
+                let client = settings
+                    .ws_client(usize::MAX)
+                    .with_cancellation(shutdown.clone())
+                    .with_sender(flashblocks_tx);
+                let stream = tokio::spawn(async move { client.stream_blocks().await });
+                tokio::spawn(LogSubscription::new(filter).run(flashblocks_rx, logs_tx));
+
+                while let Some(log) = logs_rx.recv().await {
+                    out.write(&log)?;
+                }
+                stream.await??;
+            }
+            Commands::Transfers { token, wallet } => {
+                let tracker = TransferTracker::new(settings.rpc_client())
+                    .with_tokens(token)
+                    .with_wallets(wallet);
+
+                let (flashblocks_tx, flashblocks_rx) = mpsc::unbounded_channel();
+                let (transfers_tx, mut transfers_rx) = mpsc::unbounded_channel();
+                let client = settings
+                    .ws_client(usize::MAX)
+                    .with_cancellation(shutdown.clone())
+                    .with_sender(flashblocks_tx);
+                let stream = tokio::spawn(async move { client.stream_blocks().await });
+                tokio::spawn(tracker.run(flashblocks_rx, transfers_tx));
+
+                while let Some(transfer) = transfers_rx.recv().await {
+                    out.write(&transfer)?;
+                }
+                stream.await??;
+            }
+            #[cfg(feature = "sqlite")]
+            Commands::Record { blocks, db } => {
+                let store = SqliteStore::open(&db)?;
+                let (tx, rx) = mpsc::unbounded_channel();
+                let client = settings
+                    .ws_client(blocks.unwrap_or(usize::MAX))
+                    .with_cancellation(shutdown.clone())
+                    .with_sender(tx);
+                let recorder = tokio::spawn(store.run(rx));
+                info!("Recording flashblocks to {}", db.display());
+
+                // Closing the channel lets the recorder commit what it has
+                // before an error from the feed is returned.
+                let streamed = client.stream_blocks().await;
+                drop(client);
+                let recorded = recorder.await;
+                streamed?;
+                recorded??;
+            }
+            #[cfg(feature = "sqlite")]
+            Commands::History { db, query } => {
+                let store = SqliteStore::open(&db)?;
+                match query {
+                    HistoryQuery::Tx { hash } => match store.transaction(&hash)? {
+                        Some(history) => out.write(&history)?,
+                        None => return Err(format!("Transaction {} not recorded", hash).into()),
+                    },
+                    HistoryQuery::Block { number } => {
+                        for flashblock in store.block(number)? {
+                            out.write(&flashblock)?;
+                        }
+                    }
+                }
+            }
+            #[cfg(feature = "parquet")]
+            Commands::Export { out, input, blocks } => {
+                let exporter = ParquetExporter::new(out);
+                match input {
+                    Some(input) => exporter.export_capture(&input)?,
+                    None => {
+                        let (tx, rx) = mpsc::unbounded_channel();
+                        let client = settings
+                            .ws_client(blocks.unwrap_or(usize::MAX))
+                            .with_cancellation(shutdown.clone())
+                            .with_sender(tx);
+                        let export = tokio::spawn(exporter.export_stream(rx));
+                        let streamed = client.stream_blocks().await;
+                        drop(client);
+                        let exported = export.await;
+                        streamed?;
+                        exported??;
+                    }
+                }
+            }
+            Commands::QueryLatest => {
+                let block = settings.rpc_client().fetch_latest_flashblock().await?;
+                out.write(&block)?;
+            }
+            Commands::GetBalance { address } => {
+                let balance = settings.rpc_client().get_balance(&address).await?;
+                out.write(&BalanceRecord { address, balance })?;
+            }
+            Commands::GetReceipt { tx_hash } => {
+                let receipt = settings.rpc_client().get_receipt(&tx_hash).await?;
+                out.write(&ReceiptRecord { tx_hash, receipt })?;
+            }
+            Commands::Submit {
+                private_key,
+                to,
+                value,
+                target_flashblocks,
+                node_fees,
+                priority_fee_floor,
+            } => match private_key {
+                Some(private_key) => {
+                    let fees = if node_fees {
+                        None
+                    } else {
+                        let mut estimator =
+                            FeeEstimator::new().with_priority_fee_floor(priority_fee_floor);
+                        // Used when the sampled flashblocks carry no tips.
+                        match settings.rpc_client().get_max_priority_fee_per_gas().await {
+                            Ok(fee) => estimator = estimator.with_fallback_priority_fee(fee),
+                            Err(e) => warn!("Failed to fetch the node's priority fee: {}", e),
+                        }
+                        let fees = estimator
+                            .sample(
+                                settings
+                                    .ws_client(usize::MAX)
+                                    .with_cancellation(shutdown.clone()),
+                                5,
+                                settings.timeout,
+                            )
+                            .await
+                            .estimate_fees(target_flashblocks);
+                        if fees.is_none() {
+                            warn!("No fee data from the flashblock feed; using node estimates");
+                        }
+                        fees
+                    };
+                    let request = TransferRequest {
+                        private_key,
+                        to,
+                        value,
+                        chain_id: settings.chain_id,
+                        fees,
+                    };
+                    let result = submit::submit_transfer(
+                        settings.rpc_url.clone(),
+                        request,
+                        settings.timeout,
+                    )
+                    .await?;
+                    out.write(&result)?;
+                }
+                None => submit::probe(settings.rpc_url.clone()).await?,
+            },
+        }
+
+        out.finish()?;
+        Ok(())
+    }
+}
+
+async fn serve(
+    settings: &Settings,
+    upstream: Url,
+    listen: SocketAddr,
+    shutdown: CancellationToken,
+) -> eyre::Result<()> {
+    let state = PendingState::shared();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let client = settings
+        .ws_client(usize::MAX)
+        .with_cancellation(shutdown.clone())
+        .with_sender(tx);
+    let reconnect_delay = settings.reconnect.delay();
+    let stream_shutdown = shutdown.clone();
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = client.stream_blocks().await {
+                error!("Flashblocks stream failed: {}", e);
+            }
+            if stream_shutdown.is_cancelled() {
+                break;
+            }
+            info!("Reconnecting to Flashblocks WebSocket");
+            time::sleep(reconnect_delay).await;
+        }
+    });
+
+    let state_clone = state.clone();
+    tokio::spawn(async move {
+        while let Some(flashblock) = rx.recv().await {
+            state_clone.write().await.apply(&flashblock);
+        }
+    });
+
+    PreconfServer::new(state, upstream)
+        .with_cancellation(shutdown)
+        .serve(listen)
+        .await
+}
+
+/// Cancels `shutdown` on SIGINT or SIGTERM so that streams close their
+/// connections and recorders flush. A second signal exits immediately.
+async fn cancel_on_signal(shutdown: CancellationToken) {
+    wait_for_signal().await;
+    info!("Shutting down; signal again to exit immediately");
+    shutdown.cancel();
+    wait_for_signal().await;
+    std::process::exit(130);
+}
+
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Resolves settings for `args` with `config` as the configuration file
+    /// and no `FLASHBLOCKS_*` variables set.
+    fn resolve(name: &str, config: &str, args: &[&str]) -> eyre::Result<Settings> {
+        resolve_with_env(name, config, args, &[])
+    }
+
+    fn resolve_with_env(
+        name: &str,
+        config: &str,
+        args: &[&str],
+        env: &[(&str, &str)],
+    ) -> eyre::Result<Settings> {
+        let path =
+            std::env::temp_dir().join(format!("flashblocks-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, config)?;
+        let path = path.to_string_lossy().into_owned();
+        let cli = Cli::try_parse_from(
+            ["alloy-flashblocks", "--config", path.as_str()]
+                .into_iter()
+                .chain(args.iter().copied())
+                .chain(["query-latest"]),
+        )?;
+        let env: HashMap<_, _> = env.iter().copied().collect();
+        let settings = cli
+            .global
+            .resolve_with_env(|name| env.get(name).map(|value| value.to_string()));
+        std::fs::remove_file(&path)?;
+        settings
+    }
+
+    const CONFIG: &str = r#"
+        [profiles.base-sepolia]
+        ws_url = "ws://config.example/ws"
+        timeout = 5
+
+        [profiles.base-sepolia.reconnect]
+        max_attempts = 4
+
+        [profiles.mine]
+        network = "base"
+        timeout = 3
+    "#;
+
+    #[test]
+    fn builtin_profile_is_the_fallback() {
+        let settings = resolve("builtin", "", &["--profile", "base-mainnet"]).unwrap();
+        assert_eq!(
+            settings.ws_url.as_str(),
+            "wss://mainnet.flashblocks.base.org/ws"
+        );
+        assert_eq!(settings.chain_id, Some(8453));
+    }
+
+    #[test]
+    fn config_file_overrides_builtin_profile() {
+        let settings = resolve("config", CONFIG, &[]).unwrap();
+        assert_eq!(settings.ws_url.as_str(), "ws://config.example/ws");
+        assert_eq!(
+            settings.rpc_url.as_str(),
+            "https://sepolia-preconf.base.org/"
+        );
+        assert_eq!(settings.timeout, Duration::from_secs(5));
+        assert_eq!(settings.reconnect.max_attempts, 4);
+    }
+
+    #[test]
+    fn network_preset_overrides_config_file() {
+        let settings = resolve("network", CONFIG, &["--network", "base"]).unwrap();
+        assert_eq!(
+            settings.ws_url.as_str(),
+            "wss://mainnet.flashblocks.base.org/ws"
+        );
+        assert_eq!(settings.chain_id, Some(8453));
+        assert_eq!(settings.timeout, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn env_overrides_network_preset() {
+        let env = [
+            ("FLASHBLOCKS_NETWORK", "base"),
+            ("FLASHBLOCKS_WS_URL", "ws://env.example/ws"),
+            ("FLASHBLOCKS_RECONNECT_ATTEMPTS", "6"),
+            ("FLASHBLOCKS_OUTPUT", "JSON"),
+            ("FLASHBLOCKS_CHAIN_ID", ""),
+        ];
+        let settings = resolve_with_env("env", CONFIG, &[], &env).unwrap();
+        assert_eq!(settings.ws_url.as_str(), "ws://env.example/ws");
+        assert_eq!(settings.chain_id, Some(8453));
+        assert_eq!(settings.reconnect.max_attempts, 6);
+        assert_eq!(settings.output, OutputFormat::Json);
+
+        let flagged =
+            resolve_with_env("env-flags", CONFIG, &["--reconnect-attempts", "2"], &env).unwrap();
+        assert_eq!(flagged.reconnect.max_attempts, 2);
+
+        let invalid = [("FLASHBLOCKS_TIMEOUT", "soon")];
+        let error = resolve_with_env("env-invalid", CONFIG, &[], &invalid).unwrap_err();
+        assert!(error.to_string().contains("FLASHBLOCKS_TIMEOUT"));
+    }
+
+    #[test]
+    fn flags_override_everything() {
+        let settings = resolve(
+            "flags",
+            CONFIG,
+            &[
+                "--network",
+                "base",
+                "--ws-url",
+                "ws://flag.example/ws",
+                "--timeout",
+                "7",
+                "--reconnect-attempts",
+                "9",
+            ],
+        )
+        .unwrap();
+        assert_eq!(settings.ws_url.as_str(), "ws://flag.example/ws");
+        assert_eq!(
+            settings.rpc_url.as_str(),
+            "https://mainnet-preconf.base.org/"
+        );
+        assert_eq!(settings.timeout, Duration::from_secs(7));
+        assert_eq!(settings.reconnect.max_attempts, 9);
+    }
+
+    #[test]
+    fn configured_profile_layers_on_its_network() {
+        let settings = resolve("profile", CONFIG, &["--profile", "mine"]).unwrap();
+        assert_eq!(
+            settings.ws_url.as_str(),
+            "wss://mainnet.flashblocks.base.org/ws"
+        );
+        assert_eq!(settings.timeout, Duration::from_secs(3));
+        assert!(resolve("unknown", CONFIG, &["--profile", "missing"]).is_err());
+    }
 }
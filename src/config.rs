@@ -0,0 +1,201 @@
+use crate::{network::Network, output::OutputFormat, websocket::Subscription};
+use eyre::{Result, WrapErr};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use url::Url;
+
+pub const DEFAULT_PROFILE: &str = "base-sepolia";
+pub const CONFIG_FILE_NAME: &str = "flashblocks.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReconnectPolicy {
+    pub max_attempts: u32,
+    pub delay_ms: u64,
+    pub ping_interval_ms: u64,
+    /// Reconnect when no flashblock arrives for this long
+    pub idle_timeout_ms: u64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            delay_ms: 1000,
+            ping_interval_ms: 15_000,
+            idle_timeout_ms: 10_000,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+
+    pub fn ping_interval(&self) -> Duration {
+        Duration::from_millis(self.ping_interval_ms)
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_millis(self.idle_timeout_ms)
+    }
+}
+
+/// Settings for one network. Every field is optional so that a profile in the
+/// configuration file only needs to override what differs from the built-in
+/// profile of the same name, or from the preset named by `network`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub network: Option<Network>,
+    pub ws_url: Option<Url>,
+    pub rpc_url: Option<Url>,
+    pub upstream_url: Option<Url>,
+    pub chain_id: Option<u64>,
+    pub timeout: Option<u64>,
+    pub output: Option<OutputFormat>,
+    pub metrics_port: Option<u16>,
+    pub reconnect: Option<ReconnectPolicy>,
+    pub subscription: Option<Subscription>,
+}
+
+impl Profile {
+    pub fn from_network(network: Network) -> Profile {
+        match network.preset() {
+            Some(preset) => Profile {
+                ws_url: Some(preset.ws_url),
+                rpc_url: Some(preset.rpc_url),
+                upstream_url: Some(preset.upstream_url),
+                chain_id: Some(preset.chain_id),
+                ..Default::default()
+            },
+            None => Profile::default(),
+        }
+    }
+
+    pub fn merge(self, overrides: Profile) -> Profile {
+        Profile {
+            network: overrides.network.or(self.network),
+            ws_url: overrides.ws_url.or(self.ws_url),
+            rpc_url: overrides.rpc_url.or(self.rpc_url),
+            upstream_url: overrides.upstream_url.or(self.upstream_url),
+            chain_id: overrides.chain_id.or(self.chain_id),
+            timeout: overrides.timeout.or(self.timeout),
+            output: overrides.output.or(self.output),
+            metrics_port: overrides.metrics_port.or(self.metrics_port),
+            reconnect: overrides.reconnect.or(self.reconnect),
+            subscription: overrides.subscription.or(self.subscription),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub default_profile: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Config {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .wrap_err_with(|| format!("Failed to parse config file {}", path.display()))
+    }
+
+    /// Loads the given file, or the first default location that exists:
+    /// `./flashblocks.toml`, then `$XDG_CONFIG_HOME/flashblocks/config.toml`
+    /// (falling back to `~/.config`).
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        if let Some(path) = path {
+            return Self::from_file(path);
+        }
+        match default_config_paths().into_iter().find(|p| p.is_file()) {
+            Some(path) => Self::from_file(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Resolves a profile by layering the configuration file on top of the
+    /// built-in profile of the same name.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile> {
+        let name = name
+            .or(self.default_profile.as_deref())
+            .unwrap_or(DEFAULT_PROFILE);
+        let builtin = builtin_profile(name);
+        let configured = self.profiles.get(name).cloned();
+
+        match (builtin, configured) {
+            (Some(builtin), Some(configured)) => Ok(builtin.merge(configured)),
+            (None, Some(configured)) => match configured.network {
+                Some(network) => Ok(Profile::from_network(network).merge(configured)),
+                None => Ok(configured),
+            },
+            (Some(profile), None) => Ok(profile),
+            (None, None) => Err(eyre::eyre!("Unknown profile '{}'", name)),
+        }
+    }
+}
+
+fn default_config_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(CONFIG_FILE_NAME)];
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    if let Some(config_home) = config_home {
+        paths.push(config_home.join("flashblocks").join("config.toml"));
+    }
+    paths
+}
+
+pub fn builtin_profile(name: &str) -> Option<Profile> {
+    let network = Network::from_name(name)?;
+    Some(Profile {
+        network: Some(network),
+        ..Profile::from_network(network)
+    })
+}
+
+/// Fully resolved settings shared by every command.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub ws_url: Url,
+    pub rpc_url: Url,
+    pub upstream_url: Url,
+    pub chain_id: Option<u64>,
+    pub timeout: Duration,
+    pub output: OutputFormat,
+    pub metrics_port: Option<u16>,
+    pub reconnect: ReconnectPolicy,
+    pub subscription: Subscription,
+}
+
+impl Settings {
+    pub fn from_profile(profile: Profile) -> Result<Self> {
+        Ok(Self {
+            ws_url: profile
+                .ws_url
+                .ok_or_else(|| eyre::eyre!("No WebSocket URL configured"))?,
+            rpc_url: profile
+                .rpc_url
+                .clone()
+                .ok_or_else(|| eyre::eyre!("No RPC URL configured"))?,
+            upstream_url: profile
+                .upstream_url
+                .or(profile.rpc_url)
+                .ok_or_else(|| eyre::eyre!("No upstream RPC URL configured"))?,
+            chain_id: profile.chain_id,
+            timeout: Duration::from_secs(profile.timeout.unwrap_or(30)),
+            output: profile.output.unwrap_or_default(),
+            metrics_port: profile.metrics_port,
+            reconnect: profile.reconnect.unwrap_or_default(),
+            subscription: profile.subscription.unwrap_or_default(),
+        })
+    }
+}
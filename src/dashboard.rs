@@ -0,0 +1,407 @@
+use crate::latency::LatencyTracker;
+use crate::state::PendingBlock;
+use crate::types::Flashblock;
+use crate::websocket::HealthStatus;
+use eyre::Result;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Bar, BarChart, BarGroup, Block, Borders, Cell, Gauge, Paragraph, Row, Table},
+    DefaultTerminal, Frame,
+};
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{sync::mpsc, time};
+
+const RECENT_BLOCKS: usize = 10;
+
+pub enum DashboardEvent {
+    Health(HealthStatus),
+    Disconnected(String),
+    Reconnecting,
+    Flashblock(Box<Flashblock>),
+}
+
+#[derive(Debug, Clone)]
+struct TimelineEntry {
+    index: u64,
+    transactions: usize,
+    gas_used: u64,
+}
+
+#[derive(Debug, Clone)]
+struct BlockSummary {
+    block_number: u64,
+    flashblocks: u64,
+    transactions: usize,
+    gas_used: u64,
+    gas_limit: u64,
+    duration: Duration,
+}
+
+struct CurrentBlock {
+    pending: PendingBlock,
+    timeline: Vec<TimelineEntry>,
+    started: Instant,
+    last_update: Instant,
+}
+
+impl CurrentBlock {
+    fn summary(&self) -> BlockSummary {
+        BlockSummary {
+            block_number: self.pending.block_number,
+            flashblocks: self.timeline.len() as u64,
+            transactions: self.pending.transactions.len(),
+            gas_used: self.pending.gas_used().unwrap_or_default(),
+            gas_limit: self.gas_limit(),
+            duration: self.last_update - self.started,
+        }
+    }
+
+    fn gas_limit(&self) -> u64 {
+        self.pending
+            .base
+            .as_ref()
+            .and_then(|base| base.gas_limit())
+            .unwrap_or_default()
+    }
+}
+
+/// Terminal dashboard showing the pending block as it is being built.
+pub struct Dashboard {
+    endpoint: String,
+    status: HealthStatus,
+    status_detail: String,
+    last_message: Option<Instant>,
+    reconnects: u64,
+    flashblocks_received: u64,
+    current: Option<CurrentBlock>,
+    recent: VecDeque<BlockSummary>,
+    latency: Arc<LatencyTracker>,
+}
+
+impl Dashboard {
+    /// Shows the receive lag that `latency`, fed elsewhere, records for
+    /// `endpoint`.
+    pub fn new(endpoint: impl Into<String>, latency: Arc<LatencyTracker>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            status: HealthStatus::Connecting,
+            status_detail: String::new(),
+            last_message: None,
+            reconnects: 0,
+            flashblocks_received: 0,
+            current: None,
+            recent: VecDeque::with_capacity(RECENT_BLOCKS),
+            latency,
+        }
+    }
+
+    pub async fn run(mut self, mut events: mpsc::UnboundedReceiver<DashboardEvent>) -> Result<()> {
+        let mut terminal = ratatui::init();
+        let result = self.event_loop(&mut terminal, &mut events).await;
+        ratatui::restore();
+        result
+    }
+
+    async fn event_loop(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        events: &mut mpsc::UnboundedReceiver<DashboardEvent>,
+    ) -> Result<()> {
+        let mut tick = time::interval(Duration::from_millis(100));
+
+        loop {
+            terminal.draw(|frame| self.render(frame))?;
+
+            tokio::select! {
+                event = events.recv() => match event {
+                    Some(event) => self.apply(event),
+                    None => return Ok(()),
+                },
+                _ = tick.tick() => {
+                    while event::poll(Duration::ZERO)? {
+                        if let Event::Key(key) = event::read()? {
+                            // Raw mode delivers Ctrl-C as a key press
+                            // rather than SIGINT.
+                            let interrupt = key.code == KeyCode::Char('c')
+                                && key.modifiers.contains(KeyModifiers::CONTROL);
+                            if key.kind == KeyEventKind::Press
+                                && (interrupt
+                                    || matches!(key.code, KeyCode::Char('q') | KeyCode::Esc))
+                            {
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn apply(&mut self, event: DashboardEvent) {
+        match event {
+            DashboardEvent::Health(status) => {
+                if status == HealthStatus::Connected {
+                    self.status_detail.clear();
+                }
+                self.status = status;
+            }
+            DashboardEvent::Disconnected(reason) => {
+                self.status_detail = reason;
+            }
+            DashboardEvent::Reconnecting => self.reconnects += 1,
+            DashboardEvent::Flashblock(flashblock) => self.apply_flashblock(&flashblock),
+        }
+    }
+
+    fn apply_flashblock(&mut self, flashblock: &Flashblock) {
+        // Time frames by when they arrived, not when the UI got to them.
+        let now = flashblock
+            .received
+            .map_or_else(Instant::now, |received| received.monotonic);
+        self.last_message = Some(now);
+        self.flashblocks_received += 1;
+
+        let same_payload = self
+            .current
+            .as_ref()
+            .is_some_and(|current| current.pending.payload_id == flashblock.payload_id);
+
+        if !same_payload {
+            if let Some(previous) = self.current.take() {
+                if self.recent.len() == RECENT_BLOCKS {
+                    self.recent.pop_back();
+                }
+                self.recent.push_front(previous.summary());
+            }
+            self.current = Some(CurrentBlock {
+                pending: PendingBlock::new(flashblock),
+                timeline: Vec::new(),
+                started: now,
+                last_update: now,
+            });
+        }
+
+        let current = self.current.as_mut().expect("current block just set");
+        let previous_gas = current.pending.gas_used().unwrap_or_default();
+        if same_payload {
+            current.pending.apply(flashblock);
+        }
+        let gas_used = flashblock.diff.gas_used().unwrap_or(previous_gas);
+        current.timeline.push(TimelineEntry {
+            index: flashblock.index,
+            transactions: flashblock.transaction_count(),
+            gas_used: if same_payload {
+                gas_used.saturating_sub(previous_gas)
+            } else {
+                gas_used
+            },
+        });
+        current.last_update = now;
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Length(7),
+                Constraint::Min(8),
+                Constraint::Length(RECENT_BLOCKS as u16 + 3),
+            ])
+            .split(frame.area());
+
+        self.render_health(frame, rows[0]);
+        self.render_current(frame, rows[1]);
+        self.render_timeline(frame, rows[2]);
+        self.render_recent(frame, rows[3]);
+    }
+
+    fn render_health(&self, frame: &mut Frame, area: Rect) {
+        let (label, color) = match self.status {
+            HealthStatus::Connecting => ("CONNECTING", Color::Yellow),
+            HealthStatus::Connected => ("CONNECTED", Color::Green),
+            HealthStatus::Stalled => ("STALLED", Color::Red),
+            HealthStatus::Reconnecting => ("RECONNECTING", Color::Yellow),
+            HealthStatus::Disconnected => ("DISCONNECTED", Color::Red),
+        };
+        let last_message = self
+            .last_message
+            .map(|t| format!("{}ms ago", t.elapsed().as_millis()))
+            .unwrap_or_else(|| "never".to_string());
+        let lag = self
+            .latency
+            .stats(&self.endpoint)
+            .map(|lag| format!("{:.0}/{:.0}ms", lag.p50 * 1000.0, lag.p99 * 1000.0))
+            .unwrap_or_else(|| "-".to_string());
+
+        let line = Line::from(vec![
+            Span::styled(
+                label,
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!(
+                "  {}  last message: {}  received: {}  lag p50/p99: {}  reconnects: {}  {}  (q to quit)",
+                self.endpoint,
+                last_message,
+                self.flashblocks_received,
+                lag,
+                self.reconnects,
+                self.status_detail
+            )),
+        ]);
+        frame.render_widget(
+            Paragraph::new(line).block(Block::default().borders(Borders::ALL).title("Connection")),
+            area,
+        );
+    }
+
+    fn render_current(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title("Pending block");
+        let Some(current) = &self.current else {
+            frame.render_widget(Paragraph::new("Awaiting Flashblocks...").block(block), area);
+            return;
+        };
+
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let parts = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Length(2)])
+            .split(inner);
+
+        let base_fee = current
+            .pending
+            .base
+            .as_ref()
+            .and_then(|base| base.base_fee_per_gas())
+            .map(|wei| format!("{:.4} gwei", wei as f64 / 1e9))
+            .unwrap_or_else(|| "unknown".to_string());
+        let details = vec![
+            Line::from(format!(
+                "Block #{}  payload {}  flashblock #{}",
+                current.pending.block_number, current.pending.payload_id, current.pending.index
+            )),
+            Line::from(format!(
+                "Transactions: {}  Base fee: {}  Building for {}ms",
+                current.pending.transactions.len(),
+                base_fee,
+                current.started.elapsed().as_millis()
+            )),
+        ];
+        frame.render_widget(Paragraph::new(details), parts[0]);
+
+        let gas_used = current.pending.gas_used().unwrap_or_default();
+        let gas_limit = current.gas_limit();
+        let ratio = if gas_limit > 0 {
+            (gas_used as f64 / gas_limit as f64).min(1.0)
+        } else {
+            0.0
+        };
+        frame.render_widget(
+            Gauge::default()
+                .gauge_style(Style::default().fg(Color::Cyan))
+                .ratio(ratio)
+                .label(format!(
+                    "gas {} / {} ({:.1}%)",
+                    gas_used,
+                    gas_limit,
+                    ratio * 100.0
+                )),
+            parts[1],
+        );
+    }
+
+    fn render_timeline(&self, frame: &mut Frame, area: Rect) {
+        let bars: Vec<Bar> = self
+            .current
+            .as_ref()
+            .map(|current| {
+                current
+                    .timeline
+                    .iter()
+                    .map(|entry| {
+                        Bar::default()
+                            .label(Line::from(format!(
+                                "#{} {}k",
+                                entry.index,
+                                entry.gas_used / 1000
+                            )))
+                            .value(entry.transactions as u64)
+                            .text_value(format!("{}tx", entry.transactions))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        frame.render_widget(
+            BarChart::default()
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Flashblock timeline (transactions and gas per index)"),
+                )
+                .bar_width(9)
+                .bar_gap(1)
+                .bar_style(Style::default().fg(Color::Magenta))
+                .data(BarGroup::default().bars(&bars)),
+            area,
+        );
+    }
+
+    fn render_recent(&self, frame: &mut Frame, area: Rect) {
+        let header = Row::new([
+            "Block",
+            "Flashblocks",
+            "Txs",
+            "Gas used",
+            "Fill",
+            "Duration",
+        ])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+        let rows = self.recent.iter().map(|summary| {
+            let fill = if summary.gas_limit > 0 {
+                summary.gas_used as f64 * 100.0 / summary.gas_limit as f64
+            } else {
+                0.0
+            };
+            Row::new(vec![
+                Cell::from(summary.block_number.to_string()),
+                Cell::from(summary.flashblocks.to_string()),
+                Cell::from(summary.transactions.to_string()),
+                Cell::from(summary.gas_used.to_string()),
+                Cell::from(format!("{:.1}%", fill)),
+                Cell::from(format!("{}ms", summary.duration.as_millis())),
+            ])
+        });
+
+        frame.render_widget(
+            Table::new(
+                rows,
+                [
+                    Constraint::Length(12),
+                    Constraint::Length(12),
+                    Constraint::Length(8),
+                    Constraint::Length(14),
+                    Constraint::Length(8),
+                    Constraint::Length(10),
+                ],
+            )
+            .header(header)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Recent blocks"),
+            ),
+            area,
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::types::Flashblock;
+use eyre::{bail, Result, WrapErr};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::fmt;
+use tracing::debug;
+
+/// Context attached to errors for messages that could not be decoded, so that
+/// handlers can tell them apart from connection failures.
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeError;
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Failed to decode message")
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    error: Value,
+}
+
+/// A subscription notification, `{"method": .., "params": {"subscription":
+/// .., "result": <flashblock>}}`.
+#[derive(Deserialize)]
+struct Notification {
+    params: NotificationParams,
+}
+
+#[derive(Deserialize)]
+struct NotificationParams {
+    result: Flashblock,
+}
+
+/// The response to a subscribe request, `{"id": .., "result": "0x.."}`.
+#[derive(Deserialize)]
+struct SubscriptionAck {
+    result: String,
+}
+
+fn deserialize_flashblock<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    envelope: bool,
+) -> Result<Flashblock, D::Error> {
+    if envelope {
+        Notification::deserialize(deserializer).map(|notification| notification.params.result)
+    } else {
+        Flashblock::deserialize(deserializer)
+    }
+}
+
+/// Parses a feed message straight into a [`Flashblock`]. Bare flashblocks and
+/// subscription notifications carrying one in `params.result` are both
+/// accepted. Messages that are valid JSON but not flashblocks, such as
+/// subscription acknowledgements, yield `Ok(None)`; JSON-RPC errors and
+/// invalid JSON are errors.
+///
+/// With the `simd-json` feature the message is parsed with simd-json, which
+/// uses the buffer as scratch space and leaves it modified.
+pub fn decode_flashblock(message: &mut [u8]) -> Result<Option<Flashblock>> {
+    decode(message, false)
+}
+
+/// Like [`decode_flashblock`], but tries the notification envelope first,
+/// for feeds where every flashblock arrives wrapped in one.
+pub fn decode_notification(message: &mut [u8]) -> Result<Option<Flashblock>> {
+    decode(message, true)
+}
+
+/// The subscription id of a subscribe response, if `message` is one.
+pub fn subscription_id(message: &[u8]) -> Option<String> {
+    serde_json::from_slice::<SubscriptionAck>(message)
+        .ok()
+        .map(|ack| ack.result)
+}
+
+#[cfg(not(feature = "simd-json"))]
+fn decode(message: &mut [u8], envelope: bool) -> Result<Option<Flashblock>> {
+    let parse = |envelope| {
+        deserialize_flashblock(&mut serde_json::Deserializer::from_slice(message), envelope)
+    };
+    match parse(envelope) {
+        Ok(flashblock) => Ok(Some(flashblock)),
+        Err(e) if e.is_data() => {
+            if let Ok(flashblock) = parse(!envelope) {
+                return Ok(Some(flashblock));
+            }
+            if let Ok(rpc) = serde_json::from_slice::<RpcError>(message) {
+                bail!("Received JSON-RPC error: {}", rpc.error);
+            }
+            debug!("Not a Flashblock message: {}", e);
+            debug!("Raw message: {}", String::from_utf8_lossy(message));
+            Ok(None)
+        }
+        Err(e) => Err(e).wrap_err("Failed to parse message as JSON"),
+    }
+}
+
+#[cfg(feature = "simd-json")]
+fn decode(message: &mut [u8], envelope: bool) -> Result<Option<Flashblock>> {
+    let mut deserializer =
+        simd_json::Deserializer::from_slice(message).wrap_err("Failed to parse message as JSON")?;
+    match deserialize_flashblock(&mut deserializer, envelope) {
+        Ok(flashblock) => Ok(Some(flashblock)),
+        Err(e) => {
+            deserializer.restart();
+            if let Ok(flashblock) = deserialize_flashblock(&mut deserializer, !envelope) {
+                return Ok(Some(flashblock));
+            }
+            deserializer.restart();
+            if let Ok(rpc) = RpcError::deserialize(&mut deserializer) {
+                bail!("Received JSON-RPC error: {}", rpc.error);
+            }
+            debug!("Not a Flashblock message: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn flashblock() -> Value {
+        Flashblock::builder("0x01", 2)
+            .with_gas_used(21_000)
+            .with_transactions(["0x02"])
+            .with_block_number(100)
+            .json()
+    }
+
+    fn bytes(message: &Value) -> Vec<u8> {
+        serde_json::to_vec(message).unwrap()
+    }
+
+    fn notification() -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "eth_subscription",
+            "params": { "subscription": "0xabc", "result": flashblock() },
+        })
+    }
+
+    #[test]
+    fn decodes_bare_flashblocks_and_notifications() {
+        for decode in [decode_flashblock, decode_notification] {
+            for message in [flashblock(), notification()] {
+                let decoded = decode(&mut bytes(&message)).unwrap().unwrap();
+                assert_eq!(decoded.payload_id, "0x01");
+                assert_eq!(decoded.index, 2);
+                assert_eq!(decoded.block_number(), Some(100));
+                assert_eq!(decoded.transaction_count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn skips_subscription_acknowledgements() {
+        let ack = json!({ "jsonrpc": "2.0", "id": 1, "result": "0xabc" });
+        assert!(decode_flashblock(&mut bytes(&ack)).unwrap().is_none());
+        assert!(decode_notification(&mut bytes(&ack)).unwrap().is_none());
+        assert_eq!(subscription_id(&bytes(&ack)), Some("0xabc".to_string()));
+        assert_eq!(subscription_id(&bytes(&flashblock())), None);
+    }
+
+    #[test]
+    fn rejects_rpc_errors_and_invalid_json() {
+        let error = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "error": { "code": -32601, "message": "method not found" },
+        });
+        assert!(decode_flashblock(&mut bytes(&error)).is_err());
+        assert!(decode_notification(&mut bytes(&error)).is_err());
+        assert!(decode_flashblock(&mut b"{not json".to_vec()).is_err());
+    }
+}
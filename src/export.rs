@@ -0,0 +1,678 @@
+use crate::{
+    decode::decode_flashblock,
+    server::decode_transaction,
+    state::{transaction_hash, transaction_type},
+    types::{hex_to_u64, unwrap_receipt, Flashblock},
+};
+use alloy::{consensus::Transaction, primitives::U256};
+use arrow::{
+    datatypes::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit},
+    json::ReaderBuilder,
+};
+use chrono::{DateTime, Utc};
+use eyre::{Result, WrapErr};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+/// Arrow type of a row field, so that each table's schema follows from its
+/// row struct.
+trait ArrowType {
+    fn data_type() -> DataType;
+
+    fn nullable() -> bool {
+        false
+    }
+}
+
+impl ArrowType for String {
+    fn data_type() -> DataType {
+        DataType::Utf8
+    }
+}
+
+impl ArrowType for u64 {
+    fn data_type() -> DataType {
+        DataType::UInt64
+    }
+}
+
+impl ArrowType for u8 {
+    fn data_type() -> DataType {
+        DataType::UInt8
+    }
+}
+
+impl ArrowType for DateTime<Utc> {
+    fn data_type() -> DataType {
+        DataType::Timestamp(TimeUnit::Millisecond, Some("+00:00".into()))
+    }
+}
+
+impl<T: ArrowType> ArrowType for Option<T> {
+    fn data_type() -> DataType {
+        T::data_type()
+    }
+
+    fn nullable() -> bool {
+        true
+    }
+}
+
+impl<T: ArrowType> ArrowType for Vec<T> {
+    fn data_type() -> DataType {
+        DataType::List(Arc::new(Field::new_list_field(
+            T::data_type(),
+            T::nullable(),
+        )))
+    }
+}
+
+/// A row of an exported table or of a nested struct column.
+trait Row: Serialize {
+    fn fields() -> Fields;
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(Self::fields()))
+    }
+}
+
+/// Declares a row struct along with its Arrow fields.
+macro_rules! row {
+    (struct $name:ident { $($field:ident: $ty:ty,)* }) => {
+        #[derive(Debug, Serialize)]
+        struct $name {
+            $($field: $ty,)*
+        }
+
+        impl Row for $name {
+            fn fields() -> Fields {
+                Fields::from(vec![$(
+                    Field::new(stringify!($field), <$ty>::data_type(), <$ty>::nullable()),
+                )*])
+            }
+        }
+
+        impl ArrowType for $name {
+            fn data_type() -> DataType {
+                DataType::Struct(Self::fields())
+            }
+        }
+    };
+}
+
+row! {
+    struct FlashblockRow {
+        payload_id: String,
+        index: u64,
+        block_number: Option<u64>,
+        block_timestamp: Option<u64>,
+        received_at: Option<DateTime<Utc>>,
+        parent_hash: Option<String>,
+        fee_recipient: Option<String>,
+        gas_limit: Option<u64>,
+        base_fee_per_gas: Option<String>,
+        state_root: Option<String>,
+        block_hash: Option<String>,
+        gas_used: Option<u64>,
+        transaction_count: u64,
+        withdrawal_count: u64,
+    }
+}
+
+row! {
+    struct TransactionRow {
+        payload_id: String,
+        block_number: Option<u64>,
+        flashblock_index: u64,
+        transaction_index: u64,
+        hash: String,
+        tx_type: Option<u8>,
+        from: Option<String>,
+        to: Option<String>,
+        value: Option<String>,
+        nonce: Option<u64>,
+        gas_limit: Option<u64>,
+        raw: String,
+    }
+}
+
+row! {
+    struct LogRow {
+        address: Option<String>,
+        topics: Vec<String>,
+        data: Option<String>,
+    }
+}
+
+row! {
+    struct ReceiptRow {
+        payload_id: String,
+        block_number: Option<u64>,
+        flashblock_index: u64,
+        transaction_hash: String,
+        status: Option<u64>,
+        cumulative_gas_used: Option<u64>,
+        deposit_nonce: Option<u64>,
+        logs: Vec<LogRow>,
+    }
+}
+
+row! {
+    struct BalanceChangeRow {
+        payload_id: String,
+        block_number: Option<u64>,
+        flashblock_index: u64,
+        address: String,
+        balance: String,
+    }
+}
+
+/// Rows of one table, buffered per hourly partition.
+struct Table<T> {
+    name: &'static str,
+    schema: SchemaRef,
+    partitions: BTreeMap<String, Vec<T>>,
+}
+
+impl<T: Row> Table<T> {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            schema: T::schema(),
+            partitions: BTreeMap::new(),
+        }
+    }
+
+    fn push(&mut self, partition: &str, row: T) {
+        self.partitions
+            .entry(partition.to_string())
+            .or_default()
+            .push(row);
+    }
+
+    fn rows(&self) -> usize {
+        self.partitions.values().map(Vec::len).sum()
+    }
+
+    /// Writes every buffered partition except `keep` to a new part file.
+    fn flush(&mut self, root: &Path, part: &str, keep: Option<&str>) -> Result<()> {
+        let partitions: Vec<String> = self
+            .partitions
+            .keys()
+            .filter(|p| Some(p.as_str()) != keep)
+            .cloned()
+            .collect();
+        for partition in partitions {
+            let Some(rows) = self.partitions.remove(&partition) else {
+                continue;
+            };
+            if rows.is_empty() {
+                continue;
+            }
+
+            // Strict mode rejects row fields missing from the schema instead of
+            // silently dropping them.
+            let mut decoder = ReaderBuilder::new(self.schema.clone())
+                .with_strict_mode(true)
+                .build_decoder()?;
+            decoder.serialize(&rows)?;
+            let Some(batch) = decoder.flush()? else {
+                continue;
+            };
+
+            let dir = root.join(self.name).join(&partition);
+            std::fs::create_dir_all(&dir)
+                .wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+            let path = dir.join(format!("part-{}.parquet", part));
+            let file = File::create(&path)
+                .wrap_err_with(|| format!("Failed to create {}", path.display()))?;
+            let properties = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            let mut writer = ArrowWriter::try_new(file, self.schema.clone(), Some(properties))?;
+            writer.write(&batch)?;
+            writer.close()?;
+            info!("Wrote {} rows to {}", batch.num_rows(), path.display());
+        }
+        Ok(())
+    }
+}
+
+/// Writes flashblocks to Parquet tables for flashblocks, transactions,
+/// receipts (with their logs) and balance changes, partitioned by the hour
+/// of the block timestamp: `<table>/date=YYYY-MM-DD/hour=HH/part-*.parquet`.
+pub struct ParquetExporter {
+    root: PathBuf,
+    max_rows: usize,
+    parts: u64,
+    started: i64,
+    partition: Option<String>,
+    payload_id: Option<String>,
+    block_timestamp: Option<u64>,
+    transactions_seen: u64,
+    flashblocks: Table<FlashblockRow>,
+    transactions: Table<TransactionRow>,
+    receipts: Table<ReceiptRow>,
+    balance_changes: Table<BalanceChangeRow>,
+}
+
+impl ParquetExporter {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_rows: 100_000,
+            parts: 0,
+            started: Utc::now().timestamp_millis(),
+            partition: None,
+            payload_id: None,
+            block_timestamp: None,
+            transactions_seen: 0,
+            flashblocks: Table::new("flashblocks"),
+            transactions: Table::new("transactions"),
+            receipts: Table::new("receipts"),
+            balance_changes: Table::new("balance_changes"),
+        }
+    }
+
+    /// Number of buffered rows in a table before a part file is written.
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows.max(1);
+        self
+    }
+
+    pub fn write(
+        &mut self,
+        flashblock: &Flashblock,
+        received_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        if self.payload_id.as_deref() != Some(flashblock.payload_id.as_str()) {
+            self.payload_id = Some(flashblock.payload_id.clone());
+            self.block_timestamp = None;
+            self.transactions_seen = 0;
+        }
+        if let Some(base) = &flashblock.base {
+            self.block_timestamp = base.timestamp();
+        }
+
+        let hour = self
+            .block_timestamp
+            .and_then(|ts| DateTime::from_timestamp(ts as i64, 0))
+            .or(received_at)
+            .unwrap_or_else(Utc::now);
+        let partition = hour.format("date=%Y-%m-%d/hour=%H").to_string();
+        if self.partition.as_deref() != Some(partition.as_str()) {
+            // The previous hour is complete once a later one starts.
+            self.flush(Some(&partition))?;
+            self.partition = Some(partition.clone());
+        }
+
+        let payload_id = &flashblock.payload_id;
+        let block_number = flashblock.block_number();
+        let base = flashblock.base.as_ref();
+        self.flashblocks.push(
+            &partition,
+            FlashblockRow {
+                payload_id: payload_id.clone(),
+                index: flashblock.index,
+                block_number,
+                block_timestamp: self.block_timestamp,
+                received_at,
+                parent_hash: base.map(|b| b.parent_hash.clone()),
+                fee_recipient: base.map(|b| b.fee_recipient.clone()),
+                gas_limit: base.and_then(|b| b.gas_limit()),
+                base_fee_per_gas: base
+                    .and_then(|b| b.base_fee_per_gas())
+                    .map(|fee| fee.to_string()),
+                state_root: flashblock.diff.state_root.clone(),
+                block_hash: flashblock.diff.block_hash.clone(),
+                gas_used: flashblock.diff.gas_used(),
+                transaction_count: flashblock.transaction_count() as u64,
+                withdrawal_count: flashblock.diff.withdrawals.as_ref().map_or(0, Vec::len) as u64,
+            },
+        );
+
+        for raw in flashblock.diff.transactions.iter().flatten() {
+            let Some(hash) = transaction_hash(raw) else {
+                warn!(
+                    "Skipping undecodable transaction in payload {}",
+                    flashblock.payload_id
+                );
+                continue;
+            };
+            let tx = decode_transaction(raw);
+            self.transactions.push(
+                &partition,
+                TransactionRow {
+                    payload_id: payload_id.clone(),
+                    block_number,
+                    flashblock_index: flashblock.index,
+                    transaction_index: self.transactions_seen,
+                    hash: hash.clone(),
+                    tx_type: transaction_type(raw),
+                    from: tx
+                        .as_ref()
+                        .and_then(|tx| tx.recover_signer().ok())
+                        .map(|from| from.to_string()),
+                    to: tx.as_ref().and_then(|tx| tx.to()).map(|to| to.to_string()),
+                    value: tx.as_ref().map(|tx| tx.value().to_string()),
+                    nonce: tx.as_ref().map(|tx| tx.nonce()),
+                    gas_limit: tx.as_ref().map(|tx| tx.gas_limit()),
+                    raw: raw.clone(),
+                },
+            );
+            self.transactions_seen += 1;
+
+            if let Some(receipt) = flashblock.metadata.receipt(&hash) {
+                let receipt = unwrap_receipt(receipt);
+                self.receipts.push(
+                    &partition,
+                    ReceiptRow {
+                        payload_id: payload_id.clone(),
+                        block_number,
+                        flashblock_index: flashblock.index,
+                        transaction_hash: hash,
+                        status: receipt["status"].as_str().and_then(hex_to_u64),
+                        cumulative_gas_used: receipt["cumulativeGasUsed"]
+                            .as_str()
+                            .and_then(hex_to_u64),
+                        deposit_nonce: receipt["depositNonce"].as_str().and_then(hex_to_u64),
+                        logs: receipt["logs"]
+                            .as_array()
+                            .map(|logs| logs.iter().map(log_row).collect())
+                            .unwrap_or_default(),
+                    },
+                );
+            }
+        }
+
+        if let Some(balances) = flashblock
+            .metadata
+            .new_account_balances
+            .as_ref()
+            .and_then(Value::as_object)
+        {
+            for (address, balance) in balances {
+                let Some(balance) = balance.as_str() else {
+                    continue;
+                };
+                self.balance_changes.push(
+                    &partition,
+                    BalanceChangeRow {
+                        payload_id: payload_id.clone(),
+                        block_number,
+                        flashblock_index: flashblock.index,
+                        address: address.clone(),
+                        balance: U256::from_str(balance)
+                            .map(|b| b.to_string())
+                            .unwrap_or_else(|_| balance.to_string()),
+                    },
+                );
+            }
+        }
+
+        let buffered = self.flashblocks.rows()
+            + self.transactions.rows()
+            + self.receipts.rows()
+            + self.balance_changes.rows();
+        if buffered >= self.max_rows {
+            self.flush(None)?;
+        }
+        Ok(())
+    }
+
+    /// Writes buffered rows of every partition except `keep`.
+    fn flush(&mut self, keep: Option<&str>) -> Result<()> {
+        let part = format!("{}-{:05}", self.started, self.parts);
+        self.parts += 1;
+        self.flashblocks.flush(&self.root, &part, keep)?;
+        self.transactions.flush(&self.root, &part, keep)?;
+        self.receipts.flush(&self.root, &part, keep)?;
+        self.balance_changes.flush(&self.root, &part, keep)
+    }
+
+    /// Writes all remaining rows.
+    pub fn finish(mut self) -> Result<()> {
+        self.flush(None)
+    }
+
+    /// Exports a capture of raw flashblock messages, one JSON object per line,
+    /// either bare or wrapped in subscription notifications.
+    pub fn export_capture(mut self, path: &Path) -> Result<()> {
+        let file =
+            File::open(path).wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match decode_flashblock(&mut line.into_bytes()) {
+                Ok(Some(flashblock)) => self.write(&flashblock, None)?,
+                Ok(None) => {}
+                Err(e) => warn!("Skipping line {}: {:#}", number + 1, e),
+            }
+        }
+        self.finish()
+    }
+
+    /// Exports flashblocks until the channel closes. Parquet writes block, so
+    /// this runs on the blocking thread pool.
+    pub async fn export_stream(
+        mut self,
+        mut flashblocks: mpsc::UnboundedReceiver<Flashblock>,
+    ) -> Result<()> {
+        tokio::task::spawn_blocking(move || {
+            while let Some(flashblock) = flashblocks.blocking_recv() {
+                let received_at = flashblock
+                    .received
+                    .map_or_else(Utc::now, |received| received.wall_clock);
+                self.write(&flashblock, Some(received_at))?;
+            }
+            self.finish()
+        })
+        .await?
+    }
+}
+
+fn log_row(log: &Value) -> LogRow {
+    LogRow {
+        address: log["address"].as_str().map(str::to_string),
+        topics: log["topics"]
+            .as_array()
+            .map(|topics| {
+                topics
+                    .iter()
+                    .filter_map(|t| t.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        data: log["data"].as_str().map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::FieldRef;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    /// Field names of the schema, including those of nested structs.
+    fn schema_fields(fields: &[FieldRef]) -> Vec<String> {
+        let mut names = Vec::new();
+        for field in fields {
+            names.push(field.name().clone());
+            if let DataType::List(item) = field.data_type() {
+                if let DataType::Struct(nested) = item.data_type() {
+                    names.extend(
+                        schema_fields(nested)
+                            .into_iter()
+                            .map(|n| format!("{}.{n}", field.name())),
+                    );
+                }
+            }
+        }
+        names.sort();
+        names
+    }
+
+    /// Keys of the serialized row, including those of nested objects.
+    fn row_fields(row: &Value) -> Vec<String> {
+        let mut names = Vec::new();
+        for (key, value) in row.as_object().unwrap() {
+            names.push(key.clone());
+            if let Some(nested) = value
+                .as_array()
+                .and_then(|items| items.first())
+                .filter(|item| item.is_object())
+            {
+                names.extend(row_fields(nested).into_iter().map(|n| format!("{key}.{n}")));
+            }
+        }
+        names.sort();
+        names
+    }
+
+    /// Asserts that the schema describes every field of `row` and decodes it
+    /// without loss.
+    fn assert_in_sync<T: Serialize>(schema: SchemaRef, row: T) {
+        let json = serde_json::to_value(&row).unwrap();
+        assert_eq!(schema_fields(schema.fields()), row_fields(&json));
+
+        let mut decoder = ReaderBuilder::new(schema)
+            .with_strict_mode(true)
+            .build_decoder()
+            .unwrap();
+        decoder.serialize(&[row]).unwrap();
+        let batch = decoder.flush().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert!(batch
+            .columns()
+            .iter()
+            .all(|column| column.null_count() == 0));
+    }
+
+    #[test]
+    fn flashblocks_schema_matches_rows() {
+        assert_in_sync(
+            FlashblockRow::schema(),
+            FlashblockRow {
+                payload_id: "0x01".to_string(),
+                index: 1,
+                block_number: Some(100),
+                block_timestamp: Some(1_700_000_000),
+                received_at: Some(Utc::now()),
+                parent_hash: Some("0x02".to_string()),
+                fee_recipient: Some("0x03".to_string()),
+                gas_limit: Some(30_000_000),
+                base_fee_per_gas: Some("1000".to_string()),
+                state_root: Some("0x04".to_string()),
+                block_hash: Some("0x05".to_string()),
+                gas_used: Some(21_000),
+                transaction_count: 1,
+                withdrawal_count: 0,
+            },
+        );
+    }
+
+    #[test]
+    fn transactions_schema_matches_rows() {
+        assert_in_sync(
+            TransactionRow::schema(),
+            TransactionRow {
+                payload_id: "0x01".to_string(),
+                block_number: Some(100),
+                flashblock_index: 1,
+                transaction_index: 0,
+                hash: "0x02".to_string(),
+                tx_type: Some(2),
+                from: Some("0x03".to_string()),
+                to: Some("0x04".to_string()),
+                value: Some("1".to_string()),
+                nonce: Some(7),
+                gas_limit: Some(21_000),
+                raw: "0x02f8".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn receipts_schema_matches_rows() {
+        assert_in_sync(
+            ReceiptRow::schema(),
+            ReceiptRow {
+                payload_id: "0x01".to_string(),
+                block_number: Some(100),
+                flashblock_index: 1,
+                transaction_hash: "0x02".to_string(),
+                status: Some(1),
+                cumulative_gas_used: Some(21_000),
+                deposit_nonce: Some(3),
+                logs: vec![LogRow {
+                    address: Some("0x03".to_string()),
+                    topics: vec!["0x04".to_string()],
+                    data: Some("0x".to_string()),
+                }],
+            },
+        );
+    }
+
+    #[test]
+    fn balance_changes_schema_matches_rows() {
+        assert_in_sync(
+            BalanceChangeRow::schema(),
+            BalanceChangeRow {
+                payload_id: "0x01".to_string(),
+                block_number: Some(100),
+                flashblock_index: 1,
+                address: "0x02".to_string(),
+                balance: "1000".to_string(),
+            },
+        );
+    }
+
+    #[test]
+    fn exports_bare_and_wrapped_captures() {
+        let dir = std::env::temp_dir().join(format!("flashblocks-export-{}", std::process::id()));
+        let capture = dir.join("capture.jsonl");
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = Flashblock::builder("0x01", 0).with_base().json();
+        let second = Flashblock::builder("0x01", 1).json();
+        let lines = [
+            first.to_string(),
+            serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": "0xabc" }).to_string(),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscription",
+                "params": { "subscription": "0xabc", "result": second },
+            })
+            .to_string(),
+            "{not json".to_string(),
+        ];
+        std::fs::write(&capture, lines.join("\n")).unwrap();
+
+        let output = dir.join("out");
+        ParquetExporter::new(&output)
+            .export_capture(&capture)
+            .unwrap();
+
+        let partition = output.join("flashblocks/date=1970-01-01/hour=00");
+        let part = std::fs::read_dir(&partition)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let reader = SerializedFileReader::new(File::open(part.path()).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{
+    analytics::{priority_fees, FlashblockGas, GasAnalytics},
+    types::Flashblock,
+    websocket::FlashblocksWsClient,
+};
+use alloy::eips::eip1559::BaseFeeParams;
+use serde::Serialize;
+use std::{collections::VecDeque, time::Duration};
+use tokio::{sync::mpsc, time};
+use tracing::debug;
+
+/// Flashblocks per block on Base (200ms flashblocks, 2s blocks).
+const DEFAULT_FLASHBLOCKS_PER_BLOCK: u64 = 10;
+/// Fill rate above which the remaining block space is considered contended.
+const CONGESTED_FILL_RATE: f64 = 0.8;
+
+/// Recommended EIP-1559 fees for inclusion within a number of flashblocks.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeEstimate {
+    pub block_number: Option<u64>,
+    pub target_flashblocks: u64,
+    pub base_fee_per_gas: u128,
+    pub fill_rate: Option<f64>,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+/// Estimates fees from the pending block and the priority fees paid by
+/// recently preconfirmed transactions.
+#[derive(Debug, Clone)]
+pub struct FeeEstimator {
+    analytics: GasAnalytics,
+    params: BaseFeeParams,
+    window: usize,
+    flashblocks_per_block: u64,
+    fallback_priority_fee: Option<u128>,
+    priority_fee_floor: u128,
+    tips: VecDeque<Vec<u128>>,
+    latest: Option<FlashblockGas>,
+}
+
+impl Default for FeeEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FeeEstimator {
+    pub fn new() -> Self {
+        Self {
+            analytics: GasAnalytics::new(),
+            params: BaseFeeParams::optimism_canyon(),
+            window: 20,
+            flashblocks_per_block: DEFAULT_FLASHBLOCKS_PER_BLOCK,
+            fallback_priority_fee: None,
+            priority_fee_floor: 1,
+            tips: VecDeque::new(),
+            latest: None,
+        }
+    }
+
+    pub fn with_base_fee_params(mut self, params: BaseFeeParams) -> Self {
+        self.analytics = self.analytics.with_base_fee_params(params);
+        self.params = params;
+        self
+    }
+
+    /// Number of recent flashblocks whose priority fees are sampled.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    pub fn with_flashblocks_per_block(mut self, flashblocks_per_block: u64) -> Self {
+        self.flashblocks_per_block = flashblocks_per_block.max(1);
+        self
+    }
+
+    /// Priority fee to use when no recent transaction paid one, typically the
+    /// node's `eth_maxPriorityFeePerGas`.
+    pub fn with_fallback_priority_fee(mut self, fee: u128) -> Self {
+        self.fallback_priority_fee = Some(fee);
+        self
+    }
+
+    /// Lowest priority fee ever recommended. Defaults to 1 wei.
+    pub fn with_priority_fee_floor(mut self, floor: u128) -> Self {
+        self.priority_fee_floor = floor;
+        self
+    }
+
+    /// Streams flashblocks from `client` until `count` flashblocks with a
+    /// known base fee have been observed or `wait` has elapsed.
+    pub async fn sample(
+        mut self,
+        client: FlashblocksWsClient,
+        count: usize,
+        wait: Duration,
+    ) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let cancel = client.cancellation().child_token();
+        let client = client.with_cancellation(cancel.clone()).with_sender(tx);
+        let stream = tokio::spawn(async move { client.stream_blocks().await });
+
+        let mut observed = 0;
+        let _ = time::timeout(wait, async {
+            while let Some(flashblock) = rx.recv().await {
+                self.observe(&flashblock);
+                if self.is_ready() {
+                    observed += 1;
+                }
+                if observed >= count {
+                    break;
+                }
+            }
+        })
+        .await;
+        // Cancelling closes the connection cleanly instead of dropping it.
+        cancel.cancel();
+        let _ = stream.await;
+        self
+    }
+
+    pub fn observe(&mut self, flashblock: &Flashblock) {
+        let gas = self.analytics.observe(flashblock);
+        if let Some(base_fee) = gas.base_fee_per_gas {
+            self.tips.push_back(priority_fees(flashblock, base_fee));
+            while self.tips.len() > self.window {
+                self.tips.pop_front();
+            }
+        }
+        self.latest = Some(gas);
+    }
+
+    /// Whether a base fee is known, which is required for an estimate.
+    pub fn is_ready(&self) -> bool {
+        self.latest
+            .as_ref()
+            .is_some_and(|gas| gas.base_fee_per_gas.is_some())
+    }
+
+    /// Recommends fees for inclusion within the next `target_flashblocks`
+    /// flashblocks. Tighter targets pay a higher percentile of recent
+    /// priority fees, falling back to the configured priority fee when the
+    /// window holds no tips, and the max fee covers the largest base fee increase
+    /// possible over the blocks the target spans.
+    pub fn estimate_fees(&self, target_flashblocks: u64) -> Option<FeeEstimate> {
+        let latest = self.latest.as_ref()?;
+        let base_fee = latest.base_fee_per_gas?;
+        let target = target_flashblocks.max(1);
+
+        let mut tips: Vec<u128> = self.tips.iter().flatten().copied().collect();
+        tips.sort_unstable();
+        // From the 90th percentile for the next flashblock down to the median
+        // for a full block or more.
+        let patience = (target - 1).min(self.flashblocks_per_block - 1) as f64
+            / (self.flashblocks_per_block - 1).max(1) as f64;
+        let percentile = 0.9 - 0.4 * patience;
+        let mut priority_fee = tips
+            .get(((tips.len() as f64 - 1.0) * percentile).round() as usize)
+            .copied()
+            .or(self.fallback_priority_fee)
+            .unwrap_or_default()
+            .max(self.priority_fee_floor);
+        if latest
+            .fill_rate
+            .is_some_and(|fill| fill > CONGESTED_FILL_RATE)
+        {
+            priority_fee += priority_fee / 4;
+        }
+
+        // Every block boundary crossed can raise the base fee by at most
+        // (elasticity - 1) / denominator. The transaction may miss the
+        // current block however close the target, so always cover the next.
+        let blocks_ahead = (latest.index + target) / self.flashblocks_per_block;
+        let max_increase = (self.params.elasticity_multiplier - 1) as f64
+            / self.params.max_change_denominator as f64;
+        let step = |fee: u128| fee + (fee as f64 * max_increase).ceil() as u128;
+        let mut base_fee_bound = latest
+            .predicted_next_base_fee
+            .map_or_else(|| step(base_fee), u128::from)
+            .max(base_fee);
+        for _ in 1..blocks_ahead {
+            base_fee_bound = step(base_fee_bound);
+        }
+
+        let estimate = FeeEstimate {
+            block_number: latest.block_number,
+            target_flashblocks: target,
+            base_fee_per_gas: base_fee,
+            fill_rate: latest.fill_rate,
+            max_fee_per_gas: base_fee_bound + priority_fee,
+            max_priority_fee_per_gas: priority_fee,
+        };
+        debug!("Fee estimate: {:?}", estimate);
+        Some(estimate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FlashblockBuilder;
+    use alloy::{
+        consensus::{SignableTransaction, TxEip1559, TxEnvelope},
+        eips::eip2718::Encodable2718,
+        primitives::{hex, TxKind},
+        signers::{local::PrivateKeySigner, SignerSync},
+    };
+
+    const BASE_FEE: u128 = FlashblockBuilder::BASE_FEE;
+    const GAS_LIMIT: u64 = FlashblockBuilder::GAS_LIMIT;
+
+    fn transfer(signer: &PrivateKeySigner, nonce: u64, tip: u128) -> String {
+        let tx = TxEip1559 {
+            chain_id: 8453,
+            nonce,
+            gas_limit: 21_000,
+            max_fee_per_gas: BASE_FEE * 2 + tip,
+            max_priority_fee_per_gas: tip,
+            to: TxKind::Call(signer.address()),
+            ..Default::default()
+        };
+        let signature = signer.sign_hash_sync(&tx.signature_hash()).unwrap();
+        let envelope: TxEnvelope = tx.into_signed(signature).into();
+        hex::encode_prefixed(envelope.encoded_2718())
+    }
+
+    fn flashblock(index: u64, gas_used: u64, transactions: Vec<String>) -> Flashblock {
+        Flashblock::builder("0x01", index)
+            .with_base()
+            .with_gas_used(gas_used)
+            .with_transactions(transactions)
+            .build()
+    }
+
+    #[test]
+    fn no_estimate_without_base_fee() {
+        assert!(FeeEstimator::new().estimate_fees(1).is_none());
+    }
+
+    #[test]
+    fn tighter_targets_pay_higher_percentiles() {
+        let signer = PrivateKeySigner::random();
+        let transactions = (1..=10)
+            .map(|tip| transfer(&signer, tip, tip as u128 * 1_000))
+            .collect();
+        let mut estimator = FeeEstimator::new();
+        estimator.observe(&flashblock(0, 210_000, transactions));
+
+        let next = estimator.estimate_fees(1).unwrap();
+        let block = estimator.estimate_fees(10).unwrap();
+        assert_eq!(next.max_priority_fee_per_gas, 9_000);
+        assert_eq!(block.max_priority_fee_per_gas, 6_000);
+        assert_eq!(next.base_fee_per_gas, BASE_FEE);
+        // A block below target cannot raise the next base fee.
+        assert_eq!(next.max_fee_per_gas, BASE_FEE + 9_000);
+    }
+
+    #[test]
+    fn falls_back_without_tips() {
+        let estimator = |fallback: Option<u128>| {
+            let mut estimator = FeeEstimator::new().with_priority_fee_floor(100);
+            if let Some(fallback) = fallback {
+                estimator = estimator.with_fallback_priority_fee(fallback);
+            }
+            estimator.observe(&flashblock(0, 0, Vec::new()));
+            estimator.estimate_fees(1).unwrap().max_priority_fee_per_gas
+        };
+
+        assert_eq!(estimator(Some(1_000_000)), 1_000_000);
+        assert_eq!(estimator(Some(10)), 100);
+        assert_eq!(estimator(None), 100);
+    }
+
+    #[test]
+    fn max_fee_covers_base_fee_increases() {
+        let mut estimator = FeeEstimator::new();
+        // A full block raises the next base fee by the maximum step.
+        estimator.observe(&flashblock(0, GAS_LIMIT, Vec::new()));
+        let estimate = estimator.estimate_fees(25).unwrap();
+
+        let params = BaseFeeParams::optimism_canyon();
+        let next = params.next_block_base_fee(GAS_LIMIT, GAS_LIMIT, BASE_FEE as u64) as u128;
+        assert!(next > BASE_FEE);
+        let max_increase =
+            (params.elasticity_multiplier - 1) as f64 / params.max_change_denominator as f64;
+        let bound = next + (next as f64 * max_increase).ceil() as u128;
+        assert_eq!(estimate.max_priority_fee_per_gas, 1);
+        assert_eq!(estimate.max_fee_per_gas, bound + 1);
+    }
+
+    #[test]
+    fn max_fee_covers_the_next_block_at_the_end_of_a_block() {
+        let mut estimator = FeeEstimator::new();
+        estimator.observe(&flashblock(0, GAS_LIMIT / 2, Vec::new()));
+        estimator.observe(&flashblock(
+            DEFAULT_FLASHBLOCKS_PER_BLOCK - 1,
+            GAS_LIMIT,
+            Vec::new(),
+        ));
+        let estimate = estimator.estimate_fees(1).unwrap();
+
+        let params = BaseFeeParams::optimism_canyon();
+        let next = params.next_block_base_fee(GAS_LIMIT, GAS_LIMIT, BASE_FEE as u64) as u128;
+        assert_eq!(estimate.base_fee_per_gas, BASE_FEE);
+        assert_eq!(estimate.max_fee_per_gas, next + 1);
+    }
+}
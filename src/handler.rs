@@ -0,0 +1,161 @@
+use crate::{
+    decode::DecodeError,
+    metrics::FlashblockMetrics,
+    types::{Flashblock, FlashblockBase},
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info};
+
+/// Callbacks invoked by [`crate::FlashblocksWsClient`] as the feed is
+/// consumed. Every method has an empty default, so handlers only implement
+/// the events they care about. Handlers are shared with the client's task
+/// and use interior mutability for any state they keep.
+pub trait FlashblockHandler: Send + Sync {
+    /// Called for the first flashblock of a block, which carries the base.
+    fn on_base(&self, _flashblock: &Flashblock, _base: &FlashblockBase) {}
+
+    /// Called for every flashblock, including the first of each block.
+    fn on_diff(&self, _flashblock: &Flashblock) {}
+
+    /// Called once a later payload starts or the connection ends, with the
+    /// last index received for the previous one.
+    fn on_block_complete(&self, _payload_id: &str, _last_index: u64) {}
+
+    /// Called when the connection ends, whether closed by the server or lost.
+    fn on_disconnect(&self, _reason: &str) {}
+
+    /// Called when the client schedules another connection attempt, with
+    /// the number of consecutive failed attempts so far.
+    fn on_reconnect(&self, _attempts: u32) {}
+
+    /// Called for connection failures and messages that could not be handled.
+    fn on_error(&self, _error: &eyre::Report) {}
+}
+
+impl<H: FlashblockHandler + ?Sized> FlashblockHandler for Arc<H> {
+    fn on_base(&self, flashblock: &Flashblock, base: &FlashblockBase) {
+        (**self).on_base(flashblock, base)
+    }
+
+    fn on_diff(&self, flashblock: &Flashblock) {
+        (**self).on_diff(flashblock)
+    }
+
+    fn on_block_complete(&self, payload_id: &str, last_index: u64) {
+        (**self).on_block_complete(payload_id, last_index)
+    }
+
+    fn on_disconnect(&self, reason: &str) {
+        (**self).on_disconnect(reason)
+    }
+
+    fn on_reconnect(&self, attempts: u32) {
+        (**self).on_reconnect(attempts)
+    }
+
+    fn on_error(&self, error: &eyre::Report) {
+        (**self).on_error(error)
+    }
+}
+
+/// Logs a summary of every flashblock. Registered on every client unless
+/// its handlers are replaced.
+#[derive(Debug, Default)]
+pub struct LoggingHandler {
+    blocks: AtomicUsize,
+}
+
+impl FlashblockHandler for LoggingHandler {
+    fn on_base(&self, flashblock: &Flashblock, base: &FlashblockBase) {
+        let blocks = self.blocks.fetch_add(1, Ordering::Relaxed) + 1;
+        info!("\nNew block started (#{})", blocks);
+        info!("Payload ID: {}", flashblock.payload_id);
+        if let Some(number) = base.block_number() {
+            info!("Block number: {}", number);
+        }
+        info!("Parent hash: {}", base.parent_hash);
+        info!("Gas limit: {}", base.gas_limit);
+        info!("Base fee: {} wei", base.base_fee_per_gas);
+    }
+
+    fn on_diff(&self, flashblock: &Flashblock) {
+        if !flashblock.is_initial() {
+            info!(
+                "\nDiff update #{} for payload {}",
+                flashblock.index, flashblock.payload_id
+            );
+
+            let tx_count = flashblock.transaction_count();
+            if tx_count > 0 {
+                info!("New transactions: {}", tx_count);
+            }
+
+            if let Some(gas_used) = &flashblock.diff.gas_used {
+                info!("Gas used: {}", gas_used);
+            }
+
+            if let Some(block_hash) = &flashblock.diff.block_hash {
+                info!("Block hash: {}", block_hash);
+            }
+        }
+
+        if let Some(balances) = &flashblock.metadata.new_account_balances {
+            let balance_count = balances.as_object().map_or(0, |obj| obj.len());
+            if balance_count > 0 {
+                info!("Updated balances for {} accounts", balance_count);
+            }
+        }
+
+        if let Some(receipts) = &flashblock.metadata.receipts {
+            let receipt_count = receipts.as_object().map_or(0, |obj| obj.len());
+            if receipt_count > 0 {
+                info!("New receipts: {}", receipt_count);
+            }
+        }
+    }
+
+    fn on_block_complete(&self, payload_id: &str, last_index: u64) {
+        debug!(
+            "Payload {} completed after {} flashblocks",
+            payload_id,
+            last_index + 1
+        );
+    }
+
+    fn on_disconnect(&self, reason: &str) {
+        info!("WebSocket disconnected: {}", reason);
+    }
+
+    fn on_error(&self, error: &eyre::Report) {
+        error!("{:#}", error);
+    }
+}
+
+/// Forwards every flashblock to a channel.
+impl FlashblockHandler for mpsc::UnboundedSender<Flashblock> {
+    fn on_diff(&self, flashblock: &Flashblock) {
+        if self.send(flashblock.clone()).is_err() {
+            debug!("Flashblock receiver dropped");
+        }
+    }
+}
+
+impl FlashblockHandler for FlashblockMetrics {
+    fn on_diff(&self, flashblock: &Flashblock) {
+        self.observe(flashblock);
+    }
+
+    fn on_reconnect(&self, _attempts: u32) {
+        self.reconnects.inc();
+    }
+
+    fn on_error(&self, error: &eyre::Report) {
+        if error.downcast_ref::<DecodeError>().is_some() {
+            self.parse_errors.inc();
+        }
+    }
+}
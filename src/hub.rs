@@ -0,0 +1,251 @@
+use crate::{types::Flashblock, websocket::FlashblocksWsClient};
+use eyre::Result;
+use std::sync::Arc;
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc, Notify,
+};
+use tracing::{debug, warn};
+
+/// What happens when a subscriber falls a full buffer behind the feed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LagPolicy {
+    /// Overwrite the oldest buffered flashblocks. The subscriber is told how
+    /// many it missed and carries on from the oldest one still buffered.
+    #[default]
+    DropOldest,
+    /// Close the subscriber after telling it how many flashblocks it missed.
+    Disconnect,
+    /// Stop reading the feed until every subscriber has room again.
+    Block,
+}
+
+/// Shares one WebSocket connection between any number of in-process
+/// consumers through a bounded buffer.
+pub struct FlashblocksHub {
+    client: FlashblocksWsClient,
+    publisher: Publisher,
+}
+
+/// The sending side of the hub's buffer.
+struct Publisher {
+    sender: broadcast::Sender<Arc<Flashblock>>,
+    capacity: usize,
+    policy: LagPolicy,
+    space: Arc<Notify>,
+}
+
+/// A subscription to a [`FlashblocksHub`].
+pub struct HubReceiver {
+    receiver: Option<broadcast::Receiver<Arc<Flashblock>>>,
+    policy: LagPolicy,
+    space: Arc<Notify>,
+}
+
+impl FlashblocksHub {
+    pub fn new(client: FlashblocksWsClient) -> Self {
+        let capacity = 64;
+        Self {
+            client,
+            publisher: Publisher {
+                sender: broadcast::channel(capacity).0,
+                capacity,
+                policy: LagPolicy::default(),
+                space: Arc::new(Notify::new()),
+            },
+        }
+    }
+
+    /// Minimum number of flashblocks buffered per subscriber. Set this before
+    /// subscribing; existing subscriptions are closed.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.publisher.capacity = capacity.max(1);
+        self.publisher.sender = broadcast::channel(self.publisher.capacity).0;
+        self
+    }
+
+    pub fn with_lag_policy(mut self, policy: LagPolicy) -> Self {
+        self.publisher.policy = policy;
+        self
+    }
+
+    pub fn subscribe(&self) -> HubReceiver {
+        HubReceiver {
+            receiver: Some(self.publisher.sender.subscribe()),
+            policy: self.publisher.policy,
+            space: self.publisher.space.clone(),
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.publisher.sender.receiver_count()
+    }
+
+    /// Streams from the client and publishes every flashblock until the
+    /// client stops.
+    pub async fn run(self) -> Result<()> {
+        let (tx, mut rx) = mpsc::channel(self.publisher.capacity);
+        let client = self.client.with_bounded_sender(tx);
+        let stream = tokio::spawn(async move { client.stream_blocks().await });
+
+        while let Some(flashblock) = rx.recv().await {
+            self.publisher.publish(flashblock).await;
+        }
+
+        stream.await?
+    }
+}
+
+impl Publisher {
+    async fn publish(&self, flashblock: Flashblock) {
+        if self.policy == LagPolicy::Block {
+            loop {
+                let space = self.space.notified();
+                if self.sender.len() < self.capacity {
+                    break;
+                }
+                space.await;
+            }
+        }
+        if self.sender.send(Arc::new(flashblock)).is_err() {
+            debug!("No hub subscribers");
+        }
+    }
+}
+
+impl HubReceiver {
+    /// Receives the next flashblock. A subscriber that fell behind gets
+    /// `RecvError::Lagged` with the number of flashblocks it missed; under
+    /// [`LagPolicy::Disconnect`] every later call returns `RecvError::Closed`.
+    pub async fn recv(&mut self) -> Result<Arc<Flashblock>, RecvError> {
+        let receiver = self.receiver.as_mut().ok_or(RecvError::Closed)?;
+        let result = receiver.recv().await;
+        match result {
+            Ok(_) => self.space.notify_waiters(),
+            Err(RecvError::Lagged(missed)) => {
+                warn!("Hub subscriber lagged behind by {} flashblocks", missed);
+                if self.policy == LagPolicy::Disconnect {
+                    self.receiver = None;
+                    self.space.notify_waiters();
+                }
+            }
+            Err(RecvError::Closed) => {}
+        }
+        result
+    }
+}
+
+impl Drop for HubReceiver {
+    fn drop(&mut self) {
+        self.receiver = None;
+        self.space.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+    use url::Url;
+
+    fn hub(policy: LagPolicy) -> FlashblocksHub {
+        let url = Url::parse("ws://127.0.0.1:1/ws").unwrap();
+        FlashblocksHub::new(FlashblocksWsClient::new(url, 1))
+            .with_capacity(2)
+            .with_lag_policy(policy)
+    }
+
+    async fn next_index(receiver: &mut HubReceiver) -> Result<u64, RecvError> {
+        receiver.recv().await.map(|flashblock| flashblock.index)
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_skips_to_the_oldest_buffered() {
+        let hub = hub(LagPolicy::DropOldest);
+        let mut receiver = hub.subscribe();
+        for index in 0..4 {
+            hub.publisher
+                .publish(Flashblock::builder("0x01", index).build())
+                .await;
+        }
+
+        assert_eq!(next_index(&mut receiver).await, Err(RecvError::Lagged(2)));
+        assert_eq!(next_index(&mut receiver).await, Ok(2));
+        assert_eq!(next_index(&mut receiver).await, Ok(3));
+    }
+
+    #[tokio::test]
+    async fn disconnect_closes_lagging_subscribers_only() {
+        let hub = hub(LagPolicy::Disconnect);
+        let mut slow = hub.subscribe();
+        let mut fast = hub.subscribe();
+        for index in 0..2 {
+            hub.publisher
+                .publish(Flashblock::builder("0x01", index).build())
+                .await;
+            assert_eq!(next_index(&mut fast).await, Ok(index));
+        }
+        hub.publisher
+            .publish(Flashblock::builder("0x01", 2).build())
+            .await;
+
+        assert_eq!(next_index(&mut slow).await, Err(RecvError::Lagged(1)));
+        assert_eq!(next_index(&mut slow).await, Err(RecvError::Closed));
+        assert_eq!(next_index(&mut fast).await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn block_waits_for_the_slowest_subscriber() {
+        let hub = hub(LagPolicy::Block);
+        let mut receiver = hub.subscribe();
+        hub.publisher
+            .publish(Flashblock::builder("0x01", 0).build())
+            .await;
+        hub.publisher
+            .publish(Flashblock::builder("0x01", 1).build())
+            .await;
+
+        let full = timeout(
+            Duration::from_millis(50),
+            hub.publisher
+                .publish(Flashblock::builder("0x01", 2).build()),
+        )
+        .await;
+        assert!(
+            full.is_err(),
+            "publish should wait while the buffer is full"
+        );
+
+        let ((), received) = tokio::join!(
+            hub.publisher
+                .publish(Flashblock::builder("0x01", 2).build()),
+            next_index(&mut receiver)
+        );
+        assert_eq!(received, Ok(0));
+        assert_eq!(next_index(&mut receiver).await, Ok(1));
+        assert_eq!(next_index(&mut receiver).await, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn block_resumes_when_the_subscriber_leaves() {
+        let hub = hub(LagPolicy::Block);
+        let receiver = hub.subscribe();
+        hub.publisher
+            .publish(Flashblock::builder("0x01", 0).build())
+            .await;
+        hub.publisher
+            .publish(Flashblock::builder("0x01", 1).build())
+            .await;
+
+        drop(receiver);
+        timeout(
+            Duration::from_secs(1),
+            hub.publisher
+                .publish(Flashblock::builder("0x01", 2).build()),
+        )
+        .await
+        .expect("publish should not wait without subscribers");
+        assert_eq!(hub.subscriber_count(), 0);
+    }
+}
@@ -0,0 +1,403 @@
+use crate::{handler::FlashblockHandler, metrics::FlashblockMetrics, types::Flashblock};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// Time between flashblocks on Base.
+pub const DEFAULT_FLASHBLOCK_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Time between blocks on Base.
+pub const DEFAULT_BLOCK_TIME: Duration = Duration::from_secs(2);
+
+/// When a flashblock should have been emitted: the sequencer emits one
+/// flashblock per `interval` starting at the block timestamp, so flashblock
+/// `index` is due at `timestamp + index × interval`.
+pub fn expected_emission(timestamp: u64, index: u64, interval: Duration) -> DateTime<Utc> {
+    let offset = interval.saturating_mul(u32::try_from(index).unwrap_or(u32::MAX));
+    DateTime::from_timestamp(timestamp as i64, 0).unwrap_or_default()
+        + chrono::Duration::from_std(offset).unwrap_or_default()
+}
+
+/// Distribution of receive lag, in seconds, over the most recent flashblocks
+/// of an endpoint. Negative lag means the flashblock arrived before its
+/// expected emission time.
+#[derive(Debug, Clone, Serialize)]
+pub struct LagStats {
+    pub endpoint: String,
+    pub samples: usize,
+    pub mean: f64,
+    pub min: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Default)]
+struct EndpointLag {
+    payload_id: String,
+    timestamp: Option<u64>,
+    samples: VecDeque<f64>,
+}
+
+/// Measures how long after their expected emission time flashblocks are
+/// received, per endpoint.
+#[derive(Debug)]
+pub struct LatencyTracker {
+    interval: Duration,
+    window: usize,
+    endpoints: Mutex<HashMap<String, EndpointLag>>,
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyTracker {
+    pub fn new() -> Self {
+        Self {
+            interval: DEFAULT_FLASHBLOCK_INTERVAL,
+            window: 1_000,
+            endpoints: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Number of recent flashblocks per endpoint the distribution covers.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    /// Records the lag of a flashblock received from `endpoint` and returns
+    /// it in seconds. Flashblocks without a receive time, or whose block
+    /// timestamp has not been seen, are skipped.
+    pub fn observe(&self, endpoint: &str, flashblock: &Flashblock) -> Option<f64> {
+        let mut endpoints = self.endpoints.lock().expect("latency lock poisoned");
+        let lag = endpoints.entry(endpoint.to_string()).or_default();
+        if lag.payload_id != flashblock.payload_id {
+            lag.payload_id = flashblock.payload_id.clone();
+            lag.timestamp = None;
+        }
+        if let Some(timestamp) = flashblock.base.as_ref().and_then(|base| base.timestamp()) {
+            lag.timestamp = Some(timestamp);
+        }
+
+        let received = flashblock.received?;
+        let expected = expected_emission(lag.timestamp?, flashblock.index, self.interval);
+        let seconds = (received.wall_clock - expected).num_microseconds()? as f64 / 1e6;
+        lag.samples.push_back(seconds);
+        while lag.samples.len() > self.window {
+            lag.samples.pop_front();
+        }
+        Some(seconds)
+    }
+
+    pub fn stats(&self, endpoint: &str) -> Option<LagStats> {
+        let endpoints = self.endpoints.lock().expect("latency lock poisoned");
+        lag_stats(endpoint, &endpoints.get(endpoint)?.samples)
+    }
+
+    /// Distributions of every endpoint, sorted by endpoint.
+    pub fn all_stats(&self) -> Vec<LagStats> {
+        let endpoints = self.endpoints.lock().expect("latency lock poisoned");
+        let mut stats: Vec<_> = endpoints
+            .iter()
+            .filter_map(|(endpoint, lag)| lag_stats(endpoint, &lag.samples))
+            .collect();
+        stats.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+        stats
+    }
+
+    /// A handler recording the flashblocks of one client under `endpoint`.
+    pub fn handler(self: &Arc<Self>, endpoint: impl Into<String>) -> LatencyHandler {
+        LatencyHandler {
+            tracker: Arc::clone(self),
+            endpoint: endpoint.into(),
+            metrics: None,
+        }
+    }
+}
+
+fn lag_stats(endpoint: &str, samples: &VecDeque<f64>) -> Option<LagStats> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted: Vec<f64> = samples.iter().copied().collect();
+    sorted.sort_by(f64::total_cmp);
+    let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+    Some(LagStats {
+        endpoint: endpoint.to_string(),
+        samples: sorted.len(),
+        mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+        min: sorted[0],
+        p50: percentile(0.5),
+        p90: percentile(0.9),
+        p99: percentile(0.99),
+        max: sorted[sorted.len() - 1],
+    })
+}
+
+/// Feeds a [`LatencyTracker`] from a [`crate::FlashblocksWsClient`].
+pub struct LatencyHandler {
+    tracker: Arc<LatencyTracker>,
+    endpoint: String,
+    metrics: Option<Arc<FlashblockMetrics>>,
+}
+
+impl LatencyHandler {
+    /// Also records every lag sample in the `receive_lag_seconds` histogram.
+    pub fn with_metrics(mut self, metrics: Arc<FlashblockMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+}
+
+impl FlashblockHandler for LatencyHandler {
+    fn on_diff(&self, flashblock: &Flashblock) {
+        let lag = self.tracker.observe(&self.endpoint, flashblock);
+        if let (Some(lag), Some(metrics)) = (lag, &self.metrics) {
+            metrics.receive_lag.observe(lag);
+        }
+    }
+}
+
+/// Estimates the offset of the local wall clock from the chain's block
+/// timestamps. A block's flashblocks cannot be emitted before its build window
+/// opens at `timestamp - block_time`, and network and build delays only add
+/// to the difference between receive time and that point, so the smallest
+/// difference over many blocks bounds the offset. On a synced host it is
+/// close to the minimum delivery delay; a negative estimate means the local
+/// clock is behind.
+#[derive(Debug, Clone)]
+pub struct ClockOffsetEstimator {
+    block_time: Duration,
+    window: usize,
+    min_samples: usize,
+    threshold: Duration,
+    samples: VecDeque<f64>,
+    skewed: bool,
+}
+
+impl Default for ClockOffsetEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockOffsetEstimator {
+    pub fn new() -> Self {
+        Self {
+            block_time: DEFAULT_BLOCK_TIME,
+            window: 300,
+            min_samples: 10,
+            threshold: Duration::from_secs(1),
+            samples: VecDeque::new(),
+            skewed: false,
+        }
+    }
+
+    /// Number of recent blocks the estimate covers.
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self.min_samples = self.min_samples.min(self.window);
+        self
+    }
+
+    /// Length of the build window before each block timestamp.
+    pub fn with_block_time(mut self, block_time: Duration) -> Self {
+        self.block_time = block_time;
+        self
+    }
+
+    /// Offset beyond which the local clock is reported as skewed.
+    pub fn with_threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Samples the first flashblock of each block and logs a warning when
+    /// the estimate crosses the threshold. Returns the current estimate.
+    pub fn observe(&mut self, flashblock: &Flashblock) -> Option<f64> {
+        let timestamp = flashblock.base.as_ref().and_then(|base| base.timestamp());
+        if let (Some(timestamp), Some(received)) = (timestamp, flashblock.received) {
+            let window_start = DateTime::from_timestamp(timestamp as i64, 0).unwrap_or_default()
+                - chrono::Duration::from_std(self.block_time).unwrap_or_default();
+            if let Some(micros) = (received.wall_clock - window_start).num_microseconds() {
+                self.samples.push_back(micros as f64 / 1e6);
+                while self.samples.len() > self.window {
+                    self.samples.pop_front();
+                }
+            }
+        }
+
+        let offset = self.offset()?;
+        let skewed = offset.abs() > self.threshold.as_secs_f64();
+        if skewed && !self.skewed {
+            warn!(
+                "Local clock appears to be off by {:.3}s from block timestamps (threshold {:?})",
+                offset, self.threshold
+            );
+        } else if !skewed && self.skewed {
+            info!("Local clock offset back within threshold: {:.3}s", offset);
+        }
+        self.skewed = skewed;
+        Some(offset)
+    }
+
+    /// Local clock minus chain time, in seconds, once enough blocks have
+    /// been sampled. Includes the minimum delivery delay.
+    pub fn offset(&self) -> Option<f64> {
+        if self.samples.len() < self.min_samples {
+            return None;
+        }
+        self.samples.iter().copied().min_by(f64::total_cmp)
+    }
+
+    pub fn is_skewed(&self) -> bool {
+        self.skewed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ReceiveTime;
+    use std::time::Instant;
+
+    fn received_at(timestamp: u64, millis: i64) -> ReceiveTime {
+        ReceiveTime {
+            monotonic: Instant::now(),
+            wall_clock: DateTime::from_timestamp(timestamp as i64, 0).unwrap()
+                + chrono::Duration::milliseconds(millis),
+        }
+    }
+
+    fn flashblock(payload_id: &str, index: u64, timestamp: u64, millis: i64) -> Flashblock {
+        let builder = Flashblock::builder(payload_id, index);
+        let builder = if index == 0 {
+            builder.with_timestamp(timestamp)
+        } else {
+            builder
+        };
+        builder
+            .with_received(received_at(timestamp, millis))
+            .build()
+    }
+
+    #[test]
+    fn flashblocks_are_due_one_interval_apart_from_the_timestamp() {
+        let timestamp = DateTime::from_timestamp(1_000, 0).unwrap();
+        assert_eq!(
+            expected_emission(1_000, 0, DEFAULT_FLASHBLOCK_INTERVAL),
+            timestamp
+        );
+        assert_eq!(
+            expected_emission(1_000, 9, DEFAULT_FLASHBLOCK_INTERVAL),
+            timestamp + chrono::Duration::milliseconds(1_800)
+        );
+    }
+
+    #[test]
+    fn lag_is_measured_from_the_payload_timestamp() {
+        let tracker = LatencyTracker::new();
+        assert_eq!(
+            tracker.observe("a", &flashblock("0x01", 0, 1_000, 50)),
+            Some(0.05)
+        );
+        // The last flashblock of the block, received early.
+        assert_eq!(
+            tracker.observe("a", &flashblock("0x01", 9, 1_000, 1_700)),
+            Some(-0.1)
+        );
+        // Without the base of its payload a flashblock cannot be placed.
+        assert_eq!(tracker.observe("a", &flashblock("0x02", 1, 1_002, 0)), None);
+    }
+
+    #[test]
+    fn stats_are_ordered_and_split_per_endpoint() {
+        let tracker = LatencyTracker::new();
+        for (index, millis) in [(0, 300), (1, 200), (2, 1_000), (3, 400), (4, 500)] {
+            tracker.observe(
+                "b",
+                &flashblock("0x01", index, 1_000, index as i64 * 200 + millis),
+            );
+        }
+        tracker.observe("a", &flashblock("0x01", 0, 1_000, 100));
+
+        let b = tracker.stats("b").unwrap();
+        assert_eq!(b.samples, 5);
+        assert_eq!((b.min, b.p50, b.max), (0.2, 0.4, 1.0));
+        assert!(b.min <= b.p50 && b.p50 <= b.p90 && b.p90 <= b.p99 && b.p99 <= b.max);
+        assert!((b.mean - 0.48).abs() < 1e-9);
+
+        let all = tracker.all_stats();
+        assert_eq!(
+            all.iter()
+                .map(|stats| stats.endpoint.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(all[0].samples, 1);
+        assert_eq!(all[0].p99, 0.1);
+        assert!(tracker.stats("c").is_none());
+    }
+
+    fn block(timestamp: u64, millis: i64) -> Flashblock {
+        flashblock(&format!("{timestamp:#x}"), 0, timestamp, millis)
+    }
+
+    #[test]
+    fn clock_offset_is_the_minimum_over_the_window() {
+        let mut estimator = ClockOffsetEstimator::new().with_window(3);
+        // The build window of each block opens 2s before its timestamp.
+        assert_eq!(estimator.observe(&block(100, -1_900)), None);
+        assert_eq!(estimator.observe(&block(102, -1_800)), None);
+        assert_eq!(estimator.observe(&block(104, -1_700)), Some(0.1));
+        // Flashblocks after the first do not carry a timestamp.
+        assert_eq!(
+            estimator.observe(&flashblock("0x68", 1, 104, -1_950)),
+            Some(0.1)
+        );
+        // The fastest block leaves the window.
+        assert_eq!(estimator.observe(&block(106, -1_600)), Some(0.2));
+        assert_eq!(estimator.observe(&block(108, -1_600)), Some(0.3));
+    }
+
+    #[test]
+    fn clock_is_skewed_beyond_the_threshold() {
+        let mut estimator = ClockOffsetEstimator::new()
+            .with_window(2)
+            .with_threshold(Duration::from_millis(500));
+        estimator.observe(&block(100, -1_600));
+        estimator.observe(&block(102, -1_600));
+        assert!(!estimator.is_skewed());
+
+        estimator.observe(&block(104, -1_000));
+        assert!(!estimator.is_skewed());
+        estimator.observe(&block(106, -1_000));
+        assert!(estimator.is_skewed());
+
+        // A clock that is behind is just as skewed.
+        for timestamp in [108, 110] {
+            estimator.observe(&block(timestamp, -2_400));
+        }
+        assert_eq!(estimator.offset(), Some(-0.4));
+        assert!(!estimator.is_skewed());
+        for timestamp in [112, 114] {
+            estimator.observe(&block(timestamp, -3_000));
+        }
+        assert!(estimator.is_skewed());
+    }
+}
@@ -1,9 +1,57 @@
+pub mod analytics;
 pub mod cli;
+pub mod config;
+pub mod dashboard;
+pub mod decode;
+#[cfg(feature = "parquet")]
+pub mod export;
+pub mod fees;
+pub mod handler;
+pub mod hub;
+pub mod latency;
+pub mod logs;
+pub mod metrics;
+pub mod monitor;
+pub mod network;
+pub mod output;
+pub mod reconcile;
 pub mod rpc;
+pub mod server;
+pub mod state;
+#[cfg(feature = "sqlite")]
+pub mod storage;
+pub mod submit;
+pub mod transfers;
 pub mod types;
+pub mod verify;
+pub mod watch;
 pub mod websocket;
 
+pub use analytics::*;
 pub use cli::*;
+pub use config::*;
+pub use dashboard::*;
+pub use decode::*;
+#[cfg(feature = "parquet")]
+pub use export::*;
+pub use fees::*;
+pub use handler::*;
+pub use hub::*;
+pub use latency::*;
+pub use logs::*;
+pub use metrics::*;
+pub use monitor::*;
+pub use network::*;
+pub use output::*;
+pub use reconcile::*;
 pub use rpc::*;
+pub use server::*;
+pub use state::*;
+#[cfg(feature = "sqlite")]
+pub use storage::*;
+pub use submit::*;
+pub use transfers::*;
 pub use types::*;
+pub use verify::*;
+pub use watch::*;
 pub use websocket::*;
@@ -0,0 +1,217 @@
+use crate::{
+    state::{transaction_hash, PendingBlock},
+    types::{parse_log, Flashblock},
+};
+use alloy::{
+    primitives::{Log, B256},
+    rpc::types::Filter,
+    sol_types::SolEvent,
+};
+use serde::Serialize;
+use std::str::FromStr;
+use tokio::sync::mpsc;
+
+/// A log from a preconfirmed receipt together with its position in the block.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlashblockLog {
+    pub block_number: Option<u64>,
+    pub payload_id: String,
+    pub flashblock_index: u64,
+    pub transaction_hash: B256,
+    pub transaction_index: u64,
+    pub log_index: u64,
+    #[serde(flatten)]
+    pub log: Log,
+}
+
+impl FlashblockLog {
+    /// Decodes the log as the `sol!`-generated event `E`, returning `None` if
+    /// the log is a different event.
+    pub fn decode<E: SolEvent>(&self) -> Option<E> {
+        E::decode_log_data(&self.log.data, true).ok()
+    }
+}
+
+/// Matches the logs of every flashblock against a [`Filter`]. Only the
+/// address and topic criteria of the filter are used.
+#[derive(Debug, Clone)]
+pub struct LogSubscription {
+    filter: Filter,
+    block: Option<PendingBlock>,
+}
+
+impl LogSubscription {
+    pub fn new(filter: Filter) -> Self {
+        Self {
+            filter,
+            block: None,
+        }
+    }
+
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    pub fn matches(&self, log: &Log) -> bool {
+        filter_matches(&self.filter, log)
+    }
+
+    /// Returns the matching logs of a flashblock. Flashblocks must be observed
+    /// in order so that transaction and log indices are relative to the block.
+    pub fn observe(&mut self, flashblock: &Flashblock) -> Vec<FlashblockLog> {
+        let block = match &mut self.block {
+            Some(block) if block.payload_id == flashblock.payload_id => {
+                block.apply(flashblock);
+                block
+            }
+            _ => self.block.insert(PendingBlock::new(flashblock)),
+        };
+
+        let mut matches = Vec::new();
+        for raw in flashblock.diff.transactions.iter().flatten() {
+            let Some(hash) = transaction_hash(raw) else {
+                continue;
+            };
+            let (Some(transaction_index), Some(logs)) = (
+                block.transaction_index(&hash),
+                block
+                    .receipt(&hash)
+                    .and_then(|receipt| receipt["logs"].as_array()),
+            ) else {
+                continue;
+            };
+
+            let mut log_offset = None;
+            for (i, log) in logs.iter().enumerate() {
+                let Some(log) = parse_log(log).filter(|log| filter_matches(&self.filter, log))
+                else {
+                    continue;
+                };
+                let offset = *log_offset.get_or_insert_with(|| block.log_offset(transaction_index));
+                matches.push(FlashblockLog {
+                    block_number: flashblock.block_number(),
+                    payload_id: flashblock.payload_id.clone(),
+                    flashblock_index: flashblock.index,
+                    transaction_hash: B256::from_str(&hash).unwrap_or_default(),
+                    transaction_index: transaction_index as u64,
+                    log_index: (offset + i) as u64,
+                    log,
+                });
+            }
+        }
+        matches
+    }
+
+    /// Consumes flashblocks and sends every matching log.
+    pub async fn run(
+        mut self,
+        mut flashblocks: mpsc::UnboundedReceiver<Flashblock>,
+        logs: mpsc::UnboundedSender<FlashblockLog>,
+    ) {
+        while let Some(flashblock) = flashblocks.recv().await {
+            for log in self.observe(&flashblock) {
+                if logs.send(log).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Like [`LogSubscription::run`], but only sends logs that decode as `E`.
+    pub async fn run_decoded<E: SolEvent + Send + 'static>(
+        mut self,
+        mut flashblocks: mpsc::UnboundedReceiver<Flashblock>,
+        events: mpsc::UnboundedSender<(FlashblockLog, E)>,
+    ) {
+        while let Some(flashblock) = flashblocks.recv().await {
+            for log in self.observe(&flashblock) {
+                let Some(event) = log.decode::<E>() else {
+                    continue;
+                };
+                if events.send((log, event)).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn filter_matches(filter: &Filter, log: &Log) -> bool {
+    filter.address.matches(&log.address)
+        && filter.topics.iter().enumerate().all(|(i, topic)| {
+            topic.is_empty()
+                || log
+                    .topics()
+                    .get(i)
+                    .is_some_and(|value| topic.matches(value))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::{address, b256, Address};
+    use serde_json::{json, Value};
+
+    const TOKEN: Address = address!("4200000000000000000000000000000000000006");
+    const TRANSFER: B256 =
+        b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+
+    fn log(address: Address, topic: B256) -> Value {
+        json!({ "address": address, "topics": [topic], "data": "0x" })
+    }
+
+    fn flashblock(index: u64, transactions: &[(&str, Vec<Value>)]) -> Flashblock {
+        let builder = Flashblock::builder("0x01", index)
+            .with_block_number(7)
+            .with_transactions(transactions.iter().map(|(raw, _)| *raw));
+        transactions
+            .iter()
+            .fold(builder, |builder, (raw, logs)| {
+                // Builders do not agree on the case of receipt keys.
+                let key = transaction_hash(raw)
+                    .unwrap()
+                    .to_uppercase()
+                    .replace("0X", "0x");
+                builder.with_receipt(&key, "Eip1559", json!({ "status": "0x1", "logs": logs }))
+            })
+            .build()
+    }
+
+    #[test]
+    fn matches_address_and_topics() {
+        let subscription =
+            LogSubscription::new(Filter::new().address(TOKEN).event_signature(TRANSFER));
+        let other = Address::repeat_byte(0x11);
+
+        assert!(subscription.matches(&parse_log(&log(TOKEN, TRANSFER)).unwrap()));
+        assert!(!subscription.matches(&parse_log(&log(other, TRANSFER)).unwrap()));
+        assert!(!subscription.matches(&parse_log(&log(TOKEN, B256::ZERO)).unwrap()));
+        assert!(LogSubscription::new(Filter::new())
+            .matches(&parse_log(&log(other, B256::ZERO)).unwrap()));
+    }
+
+    #[test]
+    fn indices_are_relative_to_the_payload() {
+        let mut subscription = LogSubscription::new(Filter::new().address(TOKEN));
+        let other = Address::repeat_byte(0x11);
+
+        let first = subscription.observe(&flashblock(
+            0,
+            &[
+                ("0x01", vec![log(other, TRANSFER), log(TOKEN, TRANSFER)]),
+                ("0x02", vec![]),
+            ],
+        ));
+        assert_eq!(first.len(), 1);
+        assert_eq!((first[0].transaction_index, first[0].log_index), (0, 1));
+
+        let second = subscription.observe(&flashblock(
+            1,
+            &[("0x03", vec![log(other, TRANSFER), log(TOKEN, TRANSFER)])],
+        ));
+        assert_eq!(second.len(), 1);
+        assert_eq!((second[0].transaction_index, second[0].log_index), (2, 3));
+        assert_eq!(second[0].flashblock_index, 1);
+    }
+}
@@ -0,0 +1,187 @@
+use crate::types::{Flashblock, ReceiveTime};
+use axum::{extract::State, routing::get, Router};
+use eyre::{Result, WrapErr};
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, IntCounter, Registry, TextEncoder,
+};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+#[derive(Debug, Default)]
+struct PayloadProgress {
+    payload_id: String,
+    flashblocks: u64,
+    gas_used: u64,
+    last_received: Option<Instant>,
+}
+
+/// Prometheus metrics describing the flashblock feed.
+pub struct FlashblockMetrics {
+    registry: Registry,
+    pub flashblocks_received: IntCounter,
+    pub flashblocks_per_block: Histogram,
+    pub flashblock_interval: Histogram,
+    pub transactions_per_flashblock: Histogram,
+    pub gas_per_flashblock: Histogram,
+    pub reconnects: IntCounter,
+    pub parse_errors: IntCounter,
+    /// Fed by [`crate::LatencyHandler::with_metrics`], which owns the lag
+    /// computation.
+    pub receive_lag: Histogram,
+    progress: Mutex<PayloadProgress>,
+}
+
+impl FlashblockMetrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some("flashblocks".to_string()), None)?;
+
+        let flashblocks_received =
+            IntCounter::new("received_total", "Flashblocks received from the feed")?;
+        let flashblocks_per_block = Histogram::with_opts(
+            HistogramOpts::new("per_block", "Flashblocks emitted per block")
+                .buckets((1..=20).map(f64::from).collect()),
+        )?;
+        let flashblock_interval = Histogram::with_opts(
+            HistogramOpts::new(
+                "interval_seconds",
+                "Time between consecutive flashblocks of the same block",
+            )
+            .buckets(exponential_buckets(0.01, 2.0, 10)?),
+        )?;
+        let transactions_per_flashblock = Histogram::with_opts(
+            HistogramOpts::new("transactions", "Transactions per flashblock")
+                .buckets(exponential_buckets(1.0, 2.0, 12)?),
+        )?;
+        let gas_per_flashblock = Histogram::with_opts(
+            HistogramOpts::new("gas_used", "Gas consumed per flashblock")
+                .buckets(exponential_buckets(21_000.0, 2.0, 14)?),
+        )?;
+        let reconnects = IntCounter::new("reconnects_total", "WebSocket reconnections")?;
+        let parse_errors = IntCounter::new(
+            "parse_errors_total",
+            "Messages that could not be parsed as flashblocks",
+        )?;
+        let receive_lag = Histogram::with_opts(
+            HistogramOpts::new(
+                "receive_lag_seconds",
+                "Delay between the expected emission and receipt of a flashblock",
+            )
+            // Negative lag means the flashblock arrived before it was due,
+            // which points at clock skew.
+            .buckets(
+                [-1.0, -0.5, -0.25, -0.1, 0.0]
+                    .into_iter()
+                    .chain(exponential_buckets(0.05, 2.0, 10)?)
+                    .collect(),
+            ),
+        )?;
+
+        registry.register(Box::new(flashblocks_received.clone()))?;
+        registry.register(Box::new(flashblocks_per_block.clone()))?;
+        registry.register(Box::new(flashblock_interval.clone()))?;
+        registry.register(Box::new(transactions_per_flashblock.clone()))?;
+        registry.register(Box::new(gas_per_flashblock.clone()))?;
+        registry.register(Box::new(reconnects.clone()))?;
+        registry.register(Box::new(parse_errors.clone()))?;
+        registry.register(Box::new(receive_lag.clone()))?;
+
+        Ok(Self {
+            registry,
+            flashblocks_received,
+            flashblocks_per_block,
+            flashblock_interval,
+            transactions_per_flashblock,
+            gas_per_flashblock,
+            reconnects,
+            parse_errors,
+            receive_lag,
+            progress: Mutex::new(PayloadProgress::default()),
+        })
+    }
+
+    pub fn observe(&self, flashblock: &Flashblock) {
+        let now = flashblock
+            .received
+            .unwrap_or_else(ReceiveTime::now)
+            .monotonic;
+        self.flashblocks_received.inc();
+        self.transactions_per_flashblock
+            .observe(flashblock.transaction_count() as f64);
+
+        let mut progress = self.progress.lock().expect("metrics lock poisoned");
+        if progress.payload_id != flashblock.payload_id {
+            if progress.flashblocks > 0 {
+                self.flashblocks_per_block
+                    .observe(progress.flashblocks as f64);
+            }
+            *progress = PayloadProgress {
+                payload_id: flashblock.payload_id.clone(),
+                ..Default::default()
+            };
+        } else if let Some(last) = progress.last_received {
+            self.flashblock_interval
+                .observe(now.duration_since(last).as_secs_f64());
+        }
+
+        progress.flashblocks += 1;
+        progress.last_received = Some(now);
+
+        if let Some(gas_used) = flashblock.diff.gas_used() {
+            self.gas_per_flashblock
+                .observe(gas_used.saturating_sub(progress.gas_used) as f64);
+            progress.gas_used = gas_used;
+        }
+    }
+
+    pub fn render(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+
+    /// Serves `/metrics` until `cancel` is cancelled.
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr, cancel: CancellationToken) -> Result<()> {
+        let app = Router::new()
+            .route("/metrics", get(metrics_handler))
+            .with_state(self);
+        let listener = TcpListener::bind(addr)
+            .await
+            .wrap_err("Failed to bind metrics listener")?;
+        info!("Serving Prometheus metrics on http://{}/metrics", addr);
+        axum::serve(listener, app)
+            .with_graceful_shutdown(cancel.cancelled_owned())
+            .await
+            .wrap_err("Metrics server failed")?;
+        Ok(())
+    }
+}
+
+async fn metrics_handler(State(metrics): State<Arc<FlashblockMetrics>>) -> String {
+    metrics
+        .render()
+        .unwrap_or_else(|e| format!("# failed to encode metrics: {e}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn serve_stops_on_cancellation() {
+        let metrics = Arc::new(FlashblockMetrics::new().unwrap());
+        let cancel = CancellationToken::new();
+        let addr = "127.0.0.1:0".parse().unwrap();
+        let server = tokio::spawn(metrics.serve(addr, cancel.clone()));
+
+        cancel.cancel();
+        let stopped = tokio::time::timeout(Duration::from_secs(1), server).await;
+        assert!(stopped.unwrap().unwrap().is_ok());
+    }
+}
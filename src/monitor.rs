@@ -0,0 +1,342 @@
+use crate::{
+    dashboard::{Dashboard, DashboardEvent},
+    handler::FlashblockHandler,
+    latency::{ClockOffsetEstimator, LatencyTracker},
+    metrics::FlashblockMetrics,
+    types::{Flashblock, FlashblockBase},
+    websocket::FlashblocksWsClient,
+};
+use eyre::Result;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{sync::mpsc, time};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+#[derive(Debug, Clone)]
+#[allow(dead_code)]
+struct BlockStats {
+    block_number: u64,
+    payload_id: String,
+    sub_blocks: u64,
+    total_transactions: usize,
+    start_time: Instant,
+    last_update: Instant,
+}
+
+/// Aggregates per-block statistics for the periodic log output.
+#[derive(Default)]
+struct BlockStatsHandler {
+    blocks: Mutex<HashMap<String, BlockStats>>,
+}
+
+impl FlashblockHandler for BlockStatsHandler {
+    fn on_diff(&self, flashblock: &Flashblock) {
+        process_flashblock(
+            flashblock,
+            &mut self.blocks.lock().expect("stats lock poisoned"),
+        );
+    }
+
+    fn on_disconnect(&self, reason: &str) {
+        warn!("Flashblocks stream disconnected: {}", reason);
+    }
+
+    fn on_error(&self, error: &eyre::Report) {
+        error!("{:#}", error);
+    }
+}
+
+struct ClockOffsetHandler(Mutex<ClockOffsetEstimator>);
+
+impl FlashblockHandler for ClockOffsetHandler {
+    fn on_base(&self, flashblock: &Flashblock, _base: &FlashblockBase) {
+        self.0
+            .lock()
+            .expect("clock lock poisoned")
+            .observe(flashblock);
+    }
+}
+
+/// Forwards the feed to the terminal dashboard.
+struct DashboardHandler(mpsc::UnboundedSender<DashboardEvent>);
+
+impl FlashblockHandler for DashboardHandler {
+    fn on_diff(&self, flashblock: &Flashblock) {
+        let _ = self
+            .0
+            .send(DashboardEvent::Flashblock(Box::new(flashblock.clone())));
+    }
+
+    fn on_disconnect(&self, reason: &str) {
+        let _ = self
+            .0
+            .send(DashboardEvent::Disconnected(reason.to_string()));
+    }
+
+    fn on_reconnect(&self, _attempts: u32) {
+        let _ = self.0.send(DashboardEvent::Reconnecting);
+    }
+}
+
+/// Streams flashblocks and reports per-block statistics, either as periodic
+/// log output or through the terminal dashboard.
+pub struct FlashblocksMonitor {
+    client: FlashblocksWsClient,
+    metrics_addr: Option<SocketAddr>,
+    tui: bool,
+    latency: Arc<LatencyTracker>,
+    skew_threshold: Duration,
+    cancel: CancellationToken,
+}
+
+impl FlashblocksMonitor {
+    /// Monitors the feed of `client`, which takes care of the subscription,
+    /// keepalive and reconnects. The client's handlers are replaced by the
+    /// monitor's own.
+    pub fn new(client: FlashblocksWsClient) -> Self {
+        Self {
+            client,
+            metrics_addr: None,
+            tui: false,
+            latency: Arc::new(LatencyTracker::new()),
+            skew_threshold: Duration::from_secs(1),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    pub fn with_metrics_addr(mut self, addr: Option<SocketAddr>) -> Self {
+        self.metrics_addr = addr;
+        self
+    }
+
+    pub fn with_tui(mut self, tui: bool) -> Self {
+        self.tui = tui;
+        self
+    }
+
+    /// Records receive lag into `latency`, keyed by the WebSocket URL, so
+    /// that it can be read while the monitor runs.
+    pub fn with_latency_tracker(mut self, latency: Arc<LatencyTracker>) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Offset between the local clock and block timestamps above which a
+    /// warning is logged.
+    pub fn with_skew_threshold(mut self, threshold: Duration) -> Self {
+        self.skew_threshold = threshold;
+        self
+    }
+
+    /// Stops the monitor once `cancel` is cancelled, closing the connection
+    /// and logging the final statistics.
+    pub fn with_cancellation(self, cancel: CancellationToken) -> Self {
+        Self {
+            client: self.client.with_cancellation(cancel.clone()),
+            cancel,
+            ..self
+        }
+    }
+
+    pub async fn run(self) -> Result<()> {
+        let metrics = Arc::new(FlashblockMetrics::new()?);
+        if let Some(addr) = self.metrics_addr {
+            let metrics = Arc::clone(&metrics);
+            let cancel = self.cancel.clone();
+            tokio::spawn(async move {
+                if let Err(e) = metrics.serve(addr, cancel).await {
+                    error!("Metrics server stopped: {}", e);
+                }
+            });
+        }
+
+        let endpoint = self.client.url().to_string();
+        let stats = Arc::new(BlockStatsHandler::default());
+        let clock = ClockOffsetEstimator::new().with_threshold(self.skew_threshold);
+        let latency = self
+            .latency
+            .handler(endpoint.clone())
+            .with_metrics(Arc::clone(&metrics));
+        let mut handlers: Vec<Arc<dyn FlashblockHandler>> = vec![
+            metrics,
+            Arc::new(latency),
+            Arc::new(ClockOffsetHandler(Mutex::new(clock))),
+            Arc::clone(&stats) as Arc<dyn FlashblockHandler>,
+        ];
+
+        if self.tui {
+            let (tx, rx) = mpsc::unbounded_channel();
+            handlers.push(Arc::new(DashboardHandler(tx.clone())));
+            let client = self.client.with_handlers(handlers);
+            let mut health = client.health();
+            tokio::spawn(async move {
+                while health.changed().await.is_ok() {
+                    let status = *health.borrow_and_update();
+                    if tx.send(DashboardEvent::Health(status)).is_err() {
+                        break;
+                    }
+                }
+            });
+            let stream = tokio::spawn(async move { client.stream_blocks().await });
+            let result = Dashboard::new(endpoint, Arc::clone(&self.latency))
+                .run(rx)
+                .await;
+            // Quitting the dashboard stops the stream so the connection is
+            // closed cleanly.
+            self.cancel.cancel();
+            let _ = stream.await;
+            return result;
+        }
+
+        let client = self.client.with_handlers(handlers);
+
+        let stats_clone = Arc::clone(&stats);
+        let latency = Arc::clone(&self.latency);
+        let cancel = self.cancel.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = time::sleep(Duration::from_secs(5)) => {}
+                    _ = cancel.cancelled() => return,
+                }
+                display_stats(&stats_clone, &latency);
+            }
+        });
+
+        info!("Awaiting Flashblocks...");
+        let result = client.stream_blocks().await;
+        if self.cancel.is_cancelled() {
+            info!("Monitor stopped");
+        }
+        display_stats(&stats, &self.latency);
+        result
+    }
+}
+
+fn display_stats(stats: &BlockStatsHandler, latency: &LatencyTracker) {
+    let stats_lock = stats.blocks.lock().expect("stats lock poisoned");
+    if stats_lock.is_empty() {
+        return;
+    }
+
+    info!("=== Flashblocks Statistics ===");
+
+    for (payload_id, stats) in stats_lock.iter() {
+        let duration = stats.last_update.duration_since(stats.start_time);
+        let duration_ms = duration.as_millis();
+
+        info!("Block #{}: payload_id={}", stats.block_number, payload_id);
+        info!("  Sub-blocks: {}", stats.sub_blocks);
+        info!("  Total transactions: {}", stats.total_transactions);
+        info!("  Duration: {}ms", duration_ms);
+
+        if stats.sub_blocks > 0 && duration_ms > 0 {
+            let avg_interval = duration_ms as f64 / stats.sub_blocks as f64;
+            info!("  Average sub-block interval: {:.2}ms", avg_interval);
+        }
+
+        if stats.total_transactions > 0 {
+            let tps = if duration_ms > 0 {
+                (stats.total_transactions as f64 * 1000.0) / duration_ms as f64
+            } else {
+                0.0
+            };
+            info!("  Transactions per second: {:.2}", tps);
+        }
+    }
+
+    for lag in latency.all_stats() {
+        info!(
+            "Receive lag ({}): p50={:.0}ms p90={:.0}ms p99={:.0}ms max={:.0}ms over {} flashblocks",
+            lag.endpoint,
+            lag.p50 * 1000.0,
+            lag.p90 * 1000.0,
+            lag.p99 * 1000.0,
+            lag.max * 1000.0,
+            lag.samples
+        );
+    }
+
+    info!("=============================");
+}
+
+fn process_flashblock(flashblock: &Flashblock, stats: &mut HashMap<String, BlockStats>) {
+    let now = flashblock
+        .received
+        .map_or_else(Instant::now, |received| received.monotonic);
+    let payload_id = flashblock.payload_id.clone();
+    let tx_count = flashblock
+        .diff
+        .transactions
+        .as_ref()
+        .map_or(0, |txs| txs.len());
+    let block_number = flashblock.metadata.block_number.unwrap_or_else(|| {
+        flashblock
+            .base
+            .as_ref()
+            .and_then(|base| base.block_number.strip_prefix("0x"))
+            .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+            .unwrap_or(0)
+    });
+
+    if flashblock.index == 0 {
+        info!(
+            "New block #{} started: payload_id={}",
+            block_number, payload_id
+        );
+
+        stats.insert(
+            payload_id.clone(),
+            BlockStats {
+                block_number,
+                payload_id: payload_id.clone(),
+                sub_blocks: 1,
+                total_transactions: tx_count,
+                start_time: now,
+                last_update: now,
+            },
+        );
+    } else {
+        if let Some(stat) = stats.get_mut(&payload_id) {
+            stat.sub_blocks += 1;
+            stat.total_transactions += tx_count;
+            stat.last_update = now;
+
+            debug!(
+                "Sub-block #{} for block #{}: {} transactions",
+                flashblock.index, block_number, tx_count
+            );
+        } else {
+            warn!(
+                "Received diff without initial block: payload_id={}",
+                payload_id
+            );
+            stats.insert(
+                payload_id.clone(),
+                BlockStats {
+                    block_number,
+                    payload_id: payload_id.clone(),
+                    sub_blocks: 1,
+                    total_transactions: tx_count,
+                    start_time: now,
+                    last_update: now,
+                },
+            );
+        }
+    }
+
+    if stats.len() > 10 {
+        if let Some(key) = stats
+            .iter()
+            .min_by_key(|(_, s)| s.start_time)
+            .map(|(k, _)| k.clone())
+        {
+            stats.remove(&key);
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use crate::{config::Settings, types::hex_to_u64};
+use clap::ValueEnum;
+use eyre::Result;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::{sync::mpsc, time};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use url::Url;
+
+/// Largest distance in blocks between the first flashblock and the RPC head
+/// before the endpoints are considered to belong to different chains.
+const MAX_BLOCK_DRIFT: u64 = 10;
+/// Largest distance in seconds between the first flashblock and the RPC head.
+const MAX_TIMESTAMP_DRIFT: u64 = 60;
+/// How long the check waits for a flashblock: a block and a half, so a
+/// healthy feed always delivers one.
+const MAX_FLASHBLOCK_WAIT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Network {
+    /// Base mainnet
+    Base,
+    /// Base Sepolia testnet
+    BaseSepolia,
+    /// Local OP Stack devnet
+    OpDevnet,
+    /// No preset; endpoints must be configured explicitly
+    Custom,
+}
+
+#[derive(Debug, Clone)]
+pub struct NetworkPreset {
+    pub ws_url: Url,
+    pub rpc_url: Url,
+    pub upstream_url: Url,
+    pub chain_id: u64,
+}
+
+impl Network {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "base" | "base-mainnet" => Some(Self::Base),
+            "base-sepolia" => Some(Self::BaseSepolia),
+            "op-devnet" | "local" => Some(Self::OpDevnet),
+            "custom" => Some(Self::Custom),
+            _ => None,
+        }
+    }
+
+    /// The preset whose chain id is `chain_id`.
+    pub fn from_chain_id(chain_id: u64) -> Option<Self> {
+        [Self::Base, Self::BaseSepolia, Self::OpDevnet]
+            .into_iter()
+            .find(|network| network.preset().is_some_and(|p| p.chain_id == chain_id))
+    }
+
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::Base => "Base",
+            Self::BaseSepolia => "Base Sepolia",
+            Self::OpDevnet => "OP devnet",
+            Self::Custom => "custom network",
+        }
+    }
+
+    pub fn preset(self) -> Option<NetworkPreset> {
+        let (ws_url, rpc_url, upstream_url, chain_id) = match self {
+            Self::Base => (
+                "wss://mainnet.flashblocks.base.org/ws",
+                "https://mainnet-preconf.base.org",
+                "https://mainnet.base.org",
+                8453,
+            ),
+            Self::BaseSepolia => (
+                "wss://sepolia.flashblocks.base.org/ws",
+                "https://sepolia-preconf.base.org",
+                "https://sepolia.base.org",
+                84532,
+            ),
+            Self::OpDevnet => (
+                "ws://127.0.0.1:1111/ws",
+                "http://127.0.0.1:8545",
+                "http://127.0.0.1:8545",
+                901,
+            ),
+            Self::Custom => return None,
+        };
+
+        Some(NetworkPreset {
+            ws_url: Url::parse(ws_url).ok()?,
+            rpc_url: Url::parse(rpc_url).ok()?,
+            upstream_url: Url::parse(upstream_url).ok()?,
+            chain_id,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NetworkReport {
+    pub chain_id: Option<u64>,
+    pub rpc_head: Option<u64>,
+    pub rpc_timestamp: Option<u64>,
+    pub flashblock_number: Option<u64>,
+    pub flashblock_timestamp: Option<u64>,
+}
+
+/// Checks that the configured endpoints belong to the expected chain and to
+/// each other: `eth_chainId` must match the expected chain id, and the first
+/// flashblock must be close to the RPC head in number, and in timestamp when
+/// it starts a block. Only a mismatch fails the check; endpoints that cannot
+/// be queried are logged and skipped.
+pub async fn validate(settings: &Settings) -> Result<NetworkReport> {
+    let ((chain_id, head), (flashblock_number, flashblock_timestamp)) =
+        tokio::join!(rpc_head(settings), first_flashblock(settings));
+    let chain_id = chain_id?;
+    let report = NetworkReport {
+        chain_id,
+        rpc_head: head.map(|(number, _)| number),
+        rpc_timestamp: head.map(|(_, timestamp)| timestamp),
+        flashblock_number,
+        flashblock_timestamp,
+    };
+
+    if let (Some(number), Some(rpc_head)) = (flashblock_number, report.rpc_head) {
+        if number.abs_diff(rpc_head) > MAX_BLOCK_DRIFT {
+            return Err(eyre::eyre!(
+                "Flashblock feed {} is at block {} but RPC {} is at block {}; the endpoints look mismatched",
+                settings.ws_url,
+                number,
+                settings.rpc_url,
+                rpc_head
+            ));
+        }
+    }
+    if let (Some(timestamp), Some(rpc_timestamp)) = (flashblock_timestamp, report.rpc_timestamp) {
+        if timestamp.abs_diff(rpc_timestamp) > MAX_TIMESTAMP_DRIFT {
+            return Err(eyre::eyre!(
+                "Flashblock timestamp {} differs from RPC head timestamp {} by more than {}s",
+                timestamp,
+                rpc_timestamp,
+                MAX_TIMESTAMP_DRIFT
+            ));
+        }
+    }
+
+    info!(
+        "Network check passed: chain id {:?}, RPC head {:?}, flashblock {:?}",
+        report.chain_id, report.rpc_head, flashblock_number
+    );
+    Ok(report)
+}
+
+/// Chain id and head (number and timestamp) of the RPC endpoint. Fails only
+/// when the chain id differs from the expected one.
+async fn rpc_head(settings: &Settings) -> (Result<Option<u64>>, Option<(u64, u64)>) {
+    let rpc = settings.rpc_client();
+    let (chain_id, head) =
+        tokio::join!(rpc.get_chain_id(), rpc.get_block_by_number("latest", false));
+
+    let chain_id = match chain_id {
+        Ok(chain_id) => match settings.chain_id {
+            Some(expected) if chain_id != expected => Err(eyre::eyre!(
+                "RPC endpoint {} reports chain id {} but {} was expected",
+                settings.rpc_url,
+                chain_id,
+                expected
+            )),
+            _ => Ok(Some(chain_id)),
+        },
+        Err(e) => {
+            warn!("Skipping chain id check, eth_chainId failed: {}", e);
+            Ok(None)
+        }
+    };
+
+    let head = match head {
+        Ok(Some(head)) => head["number"]
+            .as_str()
+            .and_then(hex_to_u64)
+            .zip(head["timestamp"].as_str().and_then(hex_to_u64)),
+        Ok(None) => None,
+        Err(e) => {
+            warn!(
+                "Skipping RPC head check, eth_getBlockByNumber failed: {}",
+                e
+            );
+            None
+        }
+    };
+    (chain_id, head)
+}
+
+/// Block number of the first flashblock, and its timestamp if it starts a
+/// block. Every flashblock carries its block number, so this returns as soon
+/// as one arrives.
+async fn first_flashblock(settings: &Settings) -> (Option<u64>, Option<u64>) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let cancel = CancellationToken::new();
+    let client = settings
+        .ws_client(1)
+        .with_cancellation(cancel.clone())
+        .with_sender(tx);
+    let stream = tokio::spawn(async move { client.stream_blocks().await });
+
+    let result = time::timeout(settings.timeout.min(MAX_FLASHBLOCK_WAIT), async {
+        while let Some(flashblock) = rx.recv().await {
+            if let Some(number) = flashblock.block_number() {
+                let timestamp = flashblock.base.as_ref().and_then(|base| base.timestamp());
+                return Some((Some(number), timestamp));
+            }
+        }
+        None
+    })
+    .await;
+    cancel.cancel();
+    let _ = stream.await;
+
+    match result {
+        Ok(Some(found)) => found,
+        _ => {
+            warn!(
+                "No flashblock received from {} during network check",
+                settings.ws_url
+            );
+            (None, None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Profile;
+    use axum::{extract::State, routing::post, Json, Router};
+    use serde_json::{json, Value};
+
+    /// Settings against an RPC endpoint answering `eth_chainId` with
+    /// `chain_id`, or failing every request without one, and an unreachable
+    /// feed.
+    async fn settings(chain_id: Option<u64>) -> Settings {
+        async fn handle(
+            State(chain_id): State<Option<u64>>,
+            Json(request): Json<Value>,
+        ) -> Json<Value> {
+            Json(match (chain_id, request["method"].as_str()) {
+                (Some(chain_id), Some("eth_chainId")) => {
+                    json!({ "jsonrpc": "2.0", "id": request["id"], "result": format!("{chain_id:#x}") })
+                }
+                _ => json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": { "code": -32603, "message": "internal error" },
+                }),
+            })
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rpc_url = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().route("/", post(handle)).with_state(chain_id);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Settings::from_profile(Profile {
+            ws_url: Some(Url::parse("ws://127.0.0.1:1/ws").unwrap()),
+            rpc_url: Some(Url::parse(&rpc_url).unwrap()),
+            chain_id: Some(8453),
+            timeout: Some(1),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn unreachable_endpoints_only_warn() {
+        let report = validate(&settings(None).await).await.unwrap();
+        assert_eq!(report.chain_id, None);
+        assert_eq!(report.rpc_head, None);
+        assert_eq!(report.flashblock_number, None);
+    }
+
+    #[tokio::test]
+    async fn chain_id_mismatch_fails() {
+        let error = validate(&settings(Some(1)).await).await.unwrap_err();
+        assert!(error.to_string().contains("chain id 1"));
+        assert!(validate(&settings(Some(8453)).await).await.is_ok());
+    }
+}
@@ -0,0 +1,815 @@
+use crate::analytics::FlashblockGas;
+use crate::logs::FlashblockLog;
+use crate::reconcile::{PreconfEvent, ReconcileReport};
+use crate::rpc::BlockSummary;
+#[cfg(feature = "sqlite")]
+use crate::storage::{FlashblockHistory, TransactionHistory};
+use crate::submit::SubmissionResult;
+use crate::transfers::TokenTransfer;
+use crate::types::Flashblock;
+use crate::watch::BalanceChange;
+use alloy::primitives::U256;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fmt,
+    io::{self, Stdout, Write},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// A single JSON document (an array when several records are emitted),
+    /// written once the command completes; streaming commands write ndjson
+    /// instead
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+/// A structured result that can be written in any [`OutputFormat`].
+pub trait Record: Serialize + fmt::Display {
+    const CSV_HEADER: &'static [&'static str];
+
+    fn csv_row(&self) -> Vec<String>;
+}
+
+/// Writes records to stdout in the selected format. Logs are expected to go
+/// to stderr so that stdout stays machine-readable. JSON documents are
+/// written by [`OutputWriter::finish`], or on drop if an error cut the
+/// command short.
+pub struct OutputWriter<W: Write = Stdout> {
+    format: OutputFormat,
+    out: W,
+    header_written: bool,
+    json_buffer: Vec<Value>,
+    finished: bool,
+}
+
+impl OutputWriter<Stdout> {
+    pub fn stdout(format: OutputFormat) -> Self {
+        Self::new(format, io::stdout())
+    }
+}
+
+impl<W: Write> OutputWriter<W> {
+    pub fn new(format: OutputFormat, out: W) -> Self {
+        Self {
+            format,
+            out,
+            header_written: false,
+            json_buffer: Vec::new(),
+            finished: false,
+        }
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn write<R: Record>(&mut self, record: &R) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Text => writeln!(self.out, "{}", record)?,
+            OutputFormat::Json => self.json_buffer.push(serde_json::to_value(record)?),
+            OutputFormat::Ndjson => {
+                serde_json::to_writer(&mut self.out, record)?;
+                writeln!(self.out)?;
+            }
+            OutputFormat::Csv => {
+                if !self.header_written {
+                    writeln!(self.out, "{}", R::CSV_HEADER.join(","))?;
+                    self.header_written = true;
+                }
+                let row: Vec<String> = record.csv_row().iter().map(|f| csv_escape(f)).collect();
+                writeln!(self.out, "{}", row.join(","))?;
+            }
+        }
+        self.out.flush()
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.write_document()
+    }
+
+    fn write_document(&mut self) -> io::Result<()> {
+        self.finished = true;
+        if self.format == OutputFormat::Json {
+            let document = match self.json_buffer.len() {
+                1 => self.json_buffer.remove(0),
+                _ => Value::Array(std::mem::take(&mut self.json_buffer)),
+            };
+            serde_json::to_writer_pretty(&mut self.out, &document)?;
+            writeln!(self.out)?;
+        }
+        self.out.flush()
+    }
+}
+
+impl<W: Write> Drop for OutputWriter<W> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.write_document();
+        }
+    }
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl fmt::Display for BlockSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Latest Flashblock:")?;
+        writeln!(f, "  Number: {}", self.number)?;
+        writeln!(f, "  Hash: {}", self.hash)?;
+        writeln!(f, "  Timestamp: {}", self.timestamp)?;
+        writeln!(f, "  Gas Used: {}", self.gas_used)?;
+        write!(f, "  Transactions: {}", self.transactions)
+    }
+}
+
+impl Record for BlockSummary {
+    const CSV_HEADER: &'static [&'static str] =
+        &["number", "hash", "timestamp", "gas_used", "transactions"];
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.number.to_string(),
+            self.hash.clone(),
+            self.timestamp.to_string(),
+            self.gas_used.to_string(),
+            self.transactions.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceRecord {
+    pub address: String,
+    pub balance: U256,
+}
+
+impl fmt::Display for BalanceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Balance: {}", self.balance)
+    }
+}
+
+impl Record for BalanceRecord {
+    const CSV_HEADER: &'static [&'static str] = &["address", "balance"];
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![self.address.clone(), self.balance.to_string()]
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlashblockRecord {
+    pub payload_id: String,
+    pub index: u64,
+    pub block_number: Option<u64>,
+    pub transactions: usize,
+    pub gas_used: Option<u64>,
+    pub block_hash: Option<String>,
+    pub base_fee_per_gas: Option<u128>,
+    pub receipts: usize,
+    pub balance_updates: usize,
+}
+
+impl From<&Flashblock> for FlashblockRecord {
+    fn from(flashblock: &Flashblock) -> Self {
+        let object_len = |value: &Option<Value>| {
+            value
+                .as_ref()
+                .and_then(|v| v.as_object())
+                .map_or(0, |o| o.len())
+        };
+
+        Self {
+            payload_id: flashblock.payload_id.clone(),
+            index: flashblock.index,
+            block_number: flashblock.block_number(),
+            transactions: flashblock.transaction_count(),
+            gas_used: flashblock.diff.gas_used(),
+            block_hash: flashblock.diff.block_hash.clone(),
+            base_fee_per_gas: flashblock.base.as_ref().and_then(|b| b.base_fee_per_gas()),
+            receipts: object_len(&flashblock.metadata.receipts),
+            balance_updates: object_len(&flashblock.metadata.new_account_balances),
+        }
+    }
+}
+
+impl fmt::Display for FlashblockRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let block_number = self
+            .block_number
+            .map_or_else(|| "?".to_string(), |n| n.to_string());
+        write!(
+            f,
+            "block={} payload={} index={} txs={}",
+            block_number, self.payload_id, self.index, self.transactions
+        )?;
+        if let Some(gas_used) = self.gas_used {
+            write!(f, " gas_used={}", gas_used)?;
+        }
+        if let Some(base_fee) = self.base_fee_per_gas {
+            write!(f, " base_fee={}", base_fee)?;
+        }
+        Ok(())
+    }
+}
+
+impl Record for FlashblockRecord {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "payload_id",
+        "index",
+        "block_number",
+        "transactions",
+        "gas_used",
+        "block_hash",
+        "base_fee_per_gas",
+        "receipts",
+        "balance_updates",
+    ];
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.payload_id.clone(),
+            self.index.to_string(),
+            self.block_number.map(|n| n.to_string()).unwrap_or_default(),
+            self.transactions.to_string(),
+            self.gas_used.map(|g| g.to_string()).unwrap_or_default(),
+            self.block_hash.clone().unwrap_or_default(),
+            self.base_fee_per_gas
+                .map(|b| b.to_string())
+                .unwrap_or_default(),
+            self.receipts.to_string(),
+            self.balance_updates.to_string(),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceiptRecord {
+    pub tx_hash: String,
+    pub receipt: Value,
+}
+
+impl fmt::Display for ReceiptRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let receipt = serde_json::to_string_pretty(&self.receipt).map_err(|_| fmt::Error)?;
+        write!(f, "Receipt for {}:\n{}", self.tx_hash, receipt)
+    }
+}
+
+impl Record for ReceiptRecord {
+    const CSV_HEADER: &'static [&'static str] =
+        &["tx_hash", "status", "block_number", "gas_used", "logs"];
+
+    fn csv_row(&self) -> Vec<String> {
+        let field = |name: &str| self.receipt[name].as_str().unwrap_or_default().to_string();
+        vec![
+            self.tx_hash.clone(),
+            field("status"),
+            field("blockNumber"),
+            field("gasUsed"),
+            self.receipt["logs"]
+                .as_array()
+                .map_or(0, |logs| logs.len())
+                .to_string(),
+        ]
+    }
+}
+
+impl fmt::Display for SubmissionResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Transaction {} preconfirmed after {}ms",
+            self.tx_hash, self.preconfirmed_ms
+        )?;
+        if let Some(block_number) = self.block_number {
+            write!(f, " in block {}", block_number)?;
+        }
+        Ok(())
+    }
+}
+
+impl Record for SubmissionResult {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "tx_hash",
+        "preconfirmed_ms",
+        "block_number",
+        "max_fee_per_gas",
+        "max_priority_fee_per_gas",
+    ];
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.tx_hash.clone(),
+            self.preconfirmed_ms.to_string(),
+            self.block_number.map(|n| n.to_string()).unwrap_or_default(),
+            self.max_fee_per_gas
+                .map(|n| n.to_string())
+                .unwrap_or_default(),
+            self.max_priority_fee_per_gas
+                .map(|n| n.to_string())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+impl fmt::Display for ReconcileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block={} payload={} status={:?} flashblocks={} txs={}/{}",
+            self.block_number,
+            self.payload_id,
+            self.status,
+            self.final_index + 1,
+            self.preconfirmed_transactions,
+            self.canonical_transactions
+        )?;
+        if !self.block_hash_matches {
+            write!(f, " block_hash_mismatch")?;
+        }
+        if !self.state_root_matches {
+            write!(f, " state_root_mismatch")?;
+        }
+        if !self.gas_used_matches {
+            write!(f, " gas_used_mismatch")?;
+        }
+        if let Some(roots) = &self.roots {
+            if roots.transactions_root_matches == Some(false) {
+                write!(f, " transactions_root_mismatch")?;
+            }
+            if roots.receipts_root_matches == Some(false) {
+                write!(f, " receipts_root_mismatch")?;
+            }
+        }
+        for (label, txs) in [
+            ("missing", &self.missing_transactions),
+            ("extra", &self.extra_transactions),
+            ("reordered", &self.reordered_transactions),
+        ] {
+            if !txs.is_empty() {
+                write!(f, " {}=[{}]", label, txs.join(","))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Record for ReconcileReport {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "block_number",
+        "payload_id",
+        "final_index",
+        "status",
+        "block_hash_matches",
+        "state_root_matches",
+        "gas_used_matches",
+        "transactions_root_matches",
+        "receipts_root_matches",
+        "preconfirmed_transactions",
+        "canonical_transactions",
+        "missing_transactions",
+        "extra_transactions",
+        "reordered_transactions",
+    ];
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.block_number.to_string(),
+            self.payload_id.clone(),
+            self.final_index.to_string(),
+            format!("{:?}", self.status).to_lowercase(),
+            self.block_hash_matches.to_string(),
+            self.state_root_matches.to_string(),
+            self.gas_used_matches.to_string(),
+            optional_flag(
+                self.roots
+                    .as_ref()
+                    .and_then(|r| r.transactions_root_matches),
+            ),
+            optional_flag(self.roots.as_ref().and_then(|r| r.receipts_root_matches)),
+            self.preconfirmed_transactions.to_string(),
+            self.canonical_transactions.to_string(),
+            self.missing_transactions.join(" "),
+            self.extra_transactions.join(" "),
+            self.reordered_transactions.join(" "),
+        ]
+    }
+}
+
+impl fmt::Display for PreconfEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreconfEvent::Reorg {
+                block_number,
+                payload_id,
+                canonical_block_hash,
+                ..
+            } => write!(
+                f,
+                "reorg block={} payload={} canonical_hash={}",
+                block_number,
+                payload_id,
+                canonical_block_hash.as_deref().unwrap_or("-")
+            )?,
+            PreconfEvent::PayloadDropped {
+                block_number,
+                payload_id,
+                replaced_by,
+                final_index,
+                ..
+            } => write!(
+                f,
+                "payload_dropped block={} payload={} replaced_by={} flashblocks={}",
+                block_number,
+                payload_id,
+                replaced_by,
+                final_index + 1
+            )?,
+        }
+        for tx in self.transactions() {
+            let status = tx
+                .receipt
+                .as_ref()
+                .and_then(|receipt| receipt["status"].as_str())
+                .unwrap_or("-");
+            write!(f, "\n  {} status={}", tx.hash, status)?;
+        }
+        Ok(())
+    }
+}
+
+impl Record for PreconfEvent {
+    const CSV_HEADER: &'static [&'static str] =
+        &["event", "block_number", "payload_id", "transactions"];
+
+    fn csv_row(&self) -> Vec<String> {
+        let (event, payload_id) = match self {
+            PreconfEvent::Reorg { payload_id, .. } => ("reorg", payload_id),
+            PreconfEvent::PayloadDropped { payload_id, .. } => ("payload_dropped", payload_id),
+        };
+        vec![
+            event.to_string(),
+            self.block_number().to_string(),
+            payload_id.clone(),
+            self.transactions()
+                .iter()
+                .map(|tx| tx.hash.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        ]
+    }
+}
+
+fn optional_flag(flag: Option<bool>) -> String {
+    flag.map(|flag| flag.to_string()).unwrap_or_default()
+}
+
+impl fmt::Display for BalanceChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} block={} idx={} new={} wei ({} ETH)",
+            self.address,
+            self.block_number
+                .map_or_else(|| "-".to_string(), |n| n.to_string()),
+            self.index,
+            self.new_balance,
+            self.new_balance_ether()
+        )?;
+        if let (Some(old), Some(delta), Some(delta_ether)) =
+            (self.old_balance, self.delta, self.delta_ether())
+        {
+            write!(f, " old={} delta={} wei ({} ETH)", old, delta, delta_ether)?;
+        }
+        if !self.transactions.is_empty() {
+            write!(f, " txs=[{}]", self.transactions.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl Record for BalanceChange {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "address",
+        "block_number",
+        "payload_id",
+        "index",
+        "old_balance_wei",
+        "new_balance_wei",
+        "delta_wei",
+        "old_balance_eth",
+        "new_balance_eth",
+        "delta_eth",
+        "transactions",
+    ];
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.address.to_string(),
+            self.block_number.map(|n| n.to_string()).unwrap_or_default(),
+            self.payload_id.clone(),
+            self.index.to_string(),
+            self.old_balance.map(|b| b.to_string()).unwrap_or_default(),
+            self.new_balance.to_string(),
+            self.delta.map(|d| d.to_string()).unwrap_or_default(),
+            self.old_balance_ether().unwrap_or_default(),
+            self.new_balance_ether(),
+            self.delta_ether().unwrap_or_default(),
+            self.transactions.join(" "),
+        ]
+    }
+}
+
+impl fmt::Display for FlashblockLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block={} idx={} tx={} log={} address={}",
+            self.block_number
+                .map_or_else(|| "-".to_string(), |n| n.to_string()),
+            self.flashblock_index,
+            self.transaction_hash,
+            self.log_index,
+            self.log.address
+        )?;
+        for (i, topic) in self.log.topics().iter().enumerate() {
+            write!(f, " topic{}={}", i, topic)?;
+        }
+        write!(f, " data={}", self.log.data.data)
+    }
+}
+
+impl Record for FlashblockLog {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "block_number",
+        "payload_id",
+        "flashblock_index",
+        "transaction_hash",
+        "transaction_index",
+        "log_index",
+        "address",
+        "topics",
+        "data",
+    ];
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.block_number.map(|n| n.to_string()).unwrap_or_default(),
+            self.payload_id.clone(),
+            self.flashblock_index.to_string(),
+            self.transaction_hash.to_string(),
+            self.transaction_index.to_string(),
+            self.log_index.to_string(),
+            self.log.address.to_string(),
+            self.log
+                .topics()
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            self.log.data.data.to_string(),
+        ]
+    }
+}
+
+impl fmt::Display for TokenTransfer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block={} idx={} {} {} {} -> {} tx={}",
+            self.block_number
+                .map_or_else(|| "-".to_string(), |n| n.to_string()),
+            self.flashblock_index,
+            self.amount(),
+            self.token
+                .symbol
+                .clone()
+                .unwrap_or_else(|| self.token.address.to_string()),
+            self.from,
+            self.to,
+            self.transaction_hash
+        )
+    }
+}
+
+impl Record for TokenTransfer {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "block_number",
+        "flashblock_index",
+        "transaction_hash",
+        "log_index",
+        "token",
+        "symbol",
+        "decimals",
+        "from",
+        "to",
+        "value",
+        "amount",
+    ];
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.block_number.map(|n| n.to_string()).unwrap_or_default(),
+            self.flashblock_index.to_string(),
+            self.transaction_hash.to_string(),
+            self.log_index.to_string(),
+            self.token.address.to_string(),
+            self.token.symbol.clone().unwrap_or_default(),
+            self.token
+                .decimals
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+            self.from.to_string(),
+            self.to.to_string(),
+            self.value.to_string(),
+            self.amount(),
+        ]
+    }
+}
+
+impl fmt::Display for FlashblockGas {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let gwei = |wei: u128| format!("{:.4}", wei as f64 / 1e9);
+        write!(
+            f,
+            "block={} idx={} gas={} (+{})",
+            self.block_number
+                .map_or_else(|| "-".to_string(), |n| n.to_string()),
+            self.index,
+            self.gas_used,
+            self.gas_delta
+        )?;
+        if let Some(fill_rate) = self.fill_rate {
+            write!(f, " fill={:.2}%", fill_rate * 100.0)?;
+        }
+        if let Some(base_fee) = self.base_fee_per_gas {
+            write!(f, " base_fee={} gwei", gwei(base_fee))?;
+        }
+        if let (Some(min), Some(median), Some(max)) = (
+            self.priority_fee_min,
+            self.priority_fee_median,
+            self.priority_fee_max,
+        ) {
+            write!(
+                f,
+                " tip min/median/max={}/{}/{} gwei",
+                gwei(min),
+                gwei(median),
+                gwei(max)
+            )?;
+        }
+        if let Some(next) = self.predicted_next_base_fee {
+            write!(f, " next_base_fee={} gwei", gwei(next as u128))?;
+        }
+        Ok(())
+    }
+}
+
+impl Record for FlashblockGas {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "block_number",
+        "payload_id",
+        "index",
+        "base_fee_per_gas",
+        "gas_limit",
+        "gas_used",
+        "gas_delta",
+        "fill_rate",
+        "transactions",
+        "priority_fee_min",
+        "priority_fee_median",
+        "priority_fee_max",
+        "predicted_next_base_fee",
+    ];
+
+    fn csv_row(&self) -> Vec<String> {
+        fn opt<T: ToString>(value: Option<T>) -> String {
+            value.map(|v| v.to_string()).unwrap_or_default()
+        }
+        vec![
+            opt(self.block_number),
+            self.payload_id.clone(),
+            self.index.to_string(),
+            opt(self.base_fee_per_gas),
+            opt(self.gas_limit),
+            self.gas_used.to_string(),
+            self.gas_delta.to_string(),
+            opt(self.fill_rate),
+            self.transactions.to_string(),
+            opt(self.priority_fee_min),
+            opt(self.priority_fee_median),
+            opt(self.priority_fee_max),
+            opt(self.predicted_next_base_fee),
+        ]
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl fmt::Display for TransactionHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Transaction {} first preconfirmed at {} in block {} flashblock {} (tx index {})",
+            self.hash,
+            self.first_seen_at.format("%Y-%m-%d %H:%M:%S%.3f"),
+            self.block_number
+                .map_or_else(|| "-".to_string(), |n| n.to_string()),
+            self.flashblock_index,
+            self.transaction_index
+        )?;
+        if let Some(status) = self.status {
+            write!(f, " status={}", status)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Record for TransactionHistory {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "hash",
+        "payload_id",
+        "block_number",
+        "flashblock_index",
+        "transaction_index",
+        "first_seen_at",
+        "from",
+        "to",
+        "value",
+        "status",
+    ];
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.hash.clone(),
+            self.payload_id.clone(),
+            self.block_number.map(|n| n.to_string()).unwrap_or_default(),
+            self.flashblock_index.to_string(),
+            self.transaction_index.to_string(),
+            self.first_seen_at.to_rfc3339(),
+            self.from.clone().unwrap_or_default(),
+            self.to.clone().unwrap_or_default(),
+            self.value.clone().unwrap_or_default(),
+            self.status.map(|s| s.to_string()).unwrap_or_default(),
+        ]
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl fmt::Display for FlashblockHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} block={} payload={} idx={} txs={} gas={}",
+            self.received_at.format("%H:%M:%S%.3f"),
+            self.block_number,
+            self.payload_id,
+            self.index,
+            self.transactions,
+            self.gas_used
+                .map_or_else(|| "-".to_string(), |g| g.to_string())
+        )
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Record for FlashblockHistory {
+    const CSV_HEADER: &'static [&'static str] = &[
+        "block_number",
+        "payload_id",
+        "index",
+        "received_at",
+        "transactions",
+        "gas_used",
+        "block_hash",
+    ];
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.block_number.to_string(),
+            self.payload_id.clone(),
+            self.index.to_string(),
+            self.received_at.to_rfc3339(),
+            self.transactions.to_string(),
+            self.gas_used.map(|g| g.to_string()).unwrap_or_default(),
+            self.block_hash.clone().unwrap_or_default(),
+        ]
+    }
+}
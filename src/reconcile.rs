@@ -0,0 +1,544 @@
+use crate::{
+    rpc::FlashblocksRpcClient,
+    state::{PendingBlock, PendingState},
+    types::{hex_to_u64, Flashblock},
+    verify::{verify_roots, RootVerification},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+use tokio::{sync::mpsc, time};
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconcileStatus {
+    /// The canonical block matches the last flashblock of the payload
+    Match,
+    /// The canonical block differs from what was preconfirmed
+    Divergent,
+    /// The canonical block could not be fetched before the timeout
+    Unavailable,
+}
+
+/// Comparison of an assembled payload against the sealed canonical block.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconcileReport {
+    pub block_number: u64,
+    pub payload_id: String,
+    pub final_index: u64,
+    pub status: ReconcileStatus,
+    pub preconfirmed_block_hash: Option<String>,
+    pub canonical_block_hash: Option<String>,
+    pub block_hash_matches: bool,
+    pub state_root_matches: bool,
+    pub gas_used_matches: bool,
+    pub preconfirmed_transactions: usize,
+    pub canonical_transactions: usize,
+    pub missing_transactions: Vec<String>,
+    pub extra_transactions: Vec<String>,
+    pub reordered_transactions: Vec<String>,
+    /// Transactions and receipts root check, when enabled
+    pub roots: Option<RootVerification>,
+}
+
+impl ReconcileReport {
+    pub fn is_match(&self) -> bool {
+        self.status == ReconcileStatus::Match
+    }
+
+    fn unavailable(block: &PendingBlock) -> Self {
+        Self {
+            block_number: block.block_number,
+            payload_id: block.payload_id.clone(),
+            final_index: block.index,
+            status: ReconcileStatus::Unavailable,
+            preconfirmed_block_hash: block.block_hash.clone(),
+            canonical_block_hash: None,
+            block_hash_matches: false,
+            state_root_matches: false,
+            gas_used_matches: false,
+            preconfirmed_transactions: block.transaction_hashes.len(),
+            canonical_transactions: 0,
+            missing_transactions: Vec::new(),
+            extra_transactions: Vec::new(),
+            reordered_transactions: Vec::new(),
+            roots: None,
+        }
+    }
+
+    /// Attaches a root verification, marking the report divergent if either
+    /// computed root differs from the canonical header.
+    pub fn with_roots(mut self, roots: RootVerification) -> Self {
+        if !roots.is_valid() {
+            warn!(
+                "Block {} roots differ from payload {}: transactions {:?}, receipts {:?}",
+                self.block_number,
+                self.payload_id,
+                roots.transactions_root_matches,
+                roots.receipts_root_matches
+            );
+            self.status = ReconcileStatus::Divergent;
+        }
+        self.roots = Some(roots);
+        self
+    }
+}
+
+/// A transaction that was preconfirmed but is not part of the canonical block.
+#[derive(Debug, Clone, Serialize)]
+pub struct DroppedTransaction {
+    pub hash: String,
+    pub receipt: Option<Value>,
+}
+
+/// Preconfirmations that were invalidated by the sequencer or the chain.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum PreconfEvent {
+    /// The canonical block differs from the completed payload.
+    Reorg {
+        block_number: u64,
+        payload_id: String,
+        preconfirmed_block_hash: Option<String>,
+        canonical_block_hash: Option<String>,
+        transactions: Vec<DroppedTransaction>,
+    },
+    /// The sequencer abandoned the payload and started another one for the
+    /// same height.
+    PayloadDropped {
+        block_number: u64,
+        payload_id: String,
+        replaced_by: String,
+        final_index: u64,
+        transactions: Vec<DroppedTransaction>,
+    },
+}
+
+impl PreconfEvent {
+    pub fn block_number(&self) -> u64 {
+        match self {
+            Self::Reorg { block_number, .. } | Self::PayloadDropped { block_number, .. } => {
+                *block_number
+            }
+        }
+    }
+
+    pub fn transactions(&self) -> &[DroppedTransaction] {
+        match self {
+            Self::Reorg { transactions, .. } | Self::PayloadDropped { transactions, .. } => {
+                transactions
+            }
+        }
+    }
+}
+
+/// Compares each completed payload against the canonical block with the same
+/// number once it has been sealed.
+pub struct Reconciler {
+    rpc: FlashblocksRpcClient,
+    poll_interval: Duration,
+    timeout: Duration,
+    verify_roots: bool,
+    events: Option<mpsc::UnboundedSender<PreconfEvent>>,
+}
+
+impl Reconciler {
+    pub fn new(rpc: FlashblocksRpcClient) -> Self {
+        Self {
+            rpc,
+            poll_interval: Duration::from_millis(500),
+            timeout: Duration::from_secs(30),
+            verify_roots: false,
+            events: None,
+        }
+    }
+
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Also recomputes the transactions and receipts roots of each payload and
+    /// compares them with the canonical header.
+    pub fn with_root_verification(mut self, enabled: bool) -> Self {
+        self.verify_roots = enabled;
+        self
+    }
+
+    /// Sends a [`PreconfEvent`] whenever preconfirmed transactions are
+    /// invalidated by a dropped payload or a diverging canonical block.
+    pub fn with_event_sender(mut self, events: mpsc::UnboundedSender<PreconfEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Consumes flashblocks and emits a report for every payload once the
+    /// next payload starts and its canonical block is available. Reports are
+    /// emitted in payload order, and the payload still being built when the
+    /// feed ends is reconciled last.
+    pub async fn run(
+        self,
+        mut flashblocks: mpsc::UnboundedReceiver<Flashblock>,
+        reports: mpsc::UnboundedSender<ReconcileReport>,
+    ) {
+        let mut state = PendingState::new();
+
+        while let Some(flashblock) = flashblocks.recv().await {
+            let Some(completed) = state.apply(&flashblock) else {
+                continue;
+            };
+
+            // A new payload for a height that is not above the completed one
+            // means the sequencer abandoned the completed payload.
+            let replaced_by = flashblock
+                .block_number()
+                .filter(|number| *number <= completed.block_number)
+                .map(|_| flashblock.payload_id.clone());
+            if let Some(replaced_by) = &replaced_by {
+                warn!(
+                    "Payload {} for block {} dropped in favour of {}",
+                    completed.payload_id, completed.block_number, replaced_by
+                );
+            }
+
+            if reports.is_closed() {
+                return;
+            }
+            self.emit(&completed, replaced_by, &reports).await;
+        }
+
+        if let Some(last) = state.current().filter(|_| !reports.is_closed()) {
+            self.emit(last, None, &reports).await;
+        }
+    }
+
+    async fn emit(
+        &self,
+        block: &PendingBlock,
+        replaced_by: Option<String>,
+        reports: &mpsc::UnboundedSender<ReconcileReport>,
+    ) {
+        let report = self.reconcile(block).await;
+        if let Some(event) = preconf_event(block, &report, replaced_by) {
+            if let Some(events) = &self.events {
+                let _ = events.send(event);
+            }
+        }
+        let _ = reports.send(report);
+    }
+
+    pub async fn reconcile(&self, block: &PendingBlock) -> ReconcileReport {
+        let number = format!("{:#x}", block.block_number);
+        let deadline = time::Instant::now() + self.timeout;
+
+        while time::Instant::now() < deadline {
+            let result = self
+                .rpc
+                .get_block_by_number(&number, false)
+                .await
+                .map_err(|e| e.to_string());
+            match result {
+                Ok(Some(canonical)) => {
+                    let report = compare(block, &canonical);
+                    return match self.verify_roots {
+                        true => report.with_roots(verify_roots(block, &canonical)),
+                        false => report,
+                    };
+                }
+                Ok(None) => debug!("Block {} not sealed yet", block.block_number),
+                Err(e) => warn!("Failed to fetch block {}: {}", block.block_number, e),
+            }
+            time::sleep(self.poll_interval).await;
+        }
+
+        warn!(
+            "Canonical block {} unavailable after {:?}",
+            block.block_number, self.timeout
+        );
+        ReconcileReport::unavailable(block)
+    }
+}
+
+pub fn compare(block: &PendingBlock, canonical: &Value) -> ReconcileReport {
+    let canonical_hash = canonical["hash"].as_str().map(str::to_lowercase);
+    let canonical_transactions: Vec<String> = canonical["transactions"]
+        .as_array()
+        .map(|txs| {
+            txs.iter()
+                .filter_map(|tx| tx.as_str().or_else(|| tx["hash"].as_str()))
+                .map(str::to_lowercase)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let block_hash_matches = eq_ignore_case(block.block_hash.as_deref(), canonical_hash.as_deref());
+    let state_root_matches =
+        eq_ignore_case(block.state_root.as_deref(), canonical["stateRoot"].as_str());
+    let gas_used_matches = block.gas_used().is_some()
+        && block.gas_used() == canonical["gasUsed"].as_str().and_then(hex_to_u64);
+
+    let preconfirmed = &block.transaction_hashes;
+    let preconfirmed_set: HashSet<&String> = preconfirmed.iter().collect();
+    let canonical_set: HashSet<&String> = canonical_transactions.iter().collect();
+
+    let missing_transactions: Vec<String> = preconfirmed
+        .iter()
+        .filter(|hash| !canonical_set.contains(hash))
+        .cloned()
+        .collect();
+    let extra_transactions: Vec<String> = canonical_transactions
+        .iter()
+        .filter(|hash| !preconfirmed_set.contains(hash))
+        .cloned()
+        .collect();
+
+    let reordered_transactions = reordered_transactions(preconfirmed, &canonical_transactions);
+
+    let status = if block_hash_matches
+        && state_root_matches
+        && gas_used_matches
+        && missing_transactions.is_empty()
+        && extra_transactions.is_empty()
+        && reordered_transactions.is_empty()
+    {
+        ReconcileStatus::Match
+    } else {
+        ReconcileStatus::Divergent
+    };
+
+    match status {
+        ReconcileStatus::Match => {
+            info!("Block {} matches preconfirmed payload", block.block_number)
+        }
+        _ => warn!(
+            "Block {} diverges from payload {}: {} missing, {} extra, {} reordered transactions",
+            block.block_number,
+            block.payload_id,
+            missing_transactions.len(),
+            extra_transactions.len(),
+            reordered_transactions.len()
+        ),
+    }
+
+    ReconcileReport {
+        block_number: block.block_number,
+        payload_id: block.payload_id.clone(),
+        final_index: block.index,
+        status,
+        preconfirmed_block_hash: block.block_hash.clone(),
+        canonical_block_hash: canonical_hash,
+        block_hash_matches,
+        state_root_matches,
+        gas_used_matches,
+        preconfirmed_transactions: preconfirmed.len(),
+        canonical_transactions: canonical_transactions.len(),
+        missing_transactions,
+        extra_transactions,
+        reordered_transactions,
+        roots: None,
+    }
+}
+
+/// Builds the event for a completed payload whose preconfirmations did not
+/// all make it into the canonical block.
+pub fn preconf_event(
+    block: &PendingBlock,
+    report: &ReconcileReport,
+    replaced_by: Option<String>,
+) -> Option<PreconfEvent> {
+    if report.status == ReconcileStatus::Unavailable {
+        return None;
+    }
+
+    let transactions: Vec<DroppedTransaction> = report
+        .missing_transactions
+        .iter()
+        .map(|hash| DroppedTransaction {
+            hash: hash.clone(),
+            receipt: block.receipt(hash).cloned(),
+        })
+        .collect();
+
+    match replaced_by {
+        Some(replaced_by) => Some(PreconfEvent::PayloadDropped {
+            block_number: block.block_number,
+            payload_id: block.payload_id.clone(),
+            replaced_by,
+            final_index: block.index,
+            transactions,
+        }),
+        None if !transactions.is_empty()
+            || (report.preconfirmed_block_hash.is_some() && !report.block_hash_matches) =>
+        {
+            Some(PreconfEvent::Reorg {
+                block_number: block.block_number,
+                payload_id: block.payload_id.clone(),
+                preconfirmed_block_hash: report.preconfirmed_block_hash.clone(),
+                canonical_block_hash: report.canonical_block_hash.clone(),
+                transactions,
+            })
+        }
+        None => None,
+    }
+}
+
+/// Transactions included in both blocks that changed position relative to
+/// the others.
+///
+/// The longest common subsequence of the two orderings is kept in place and
+/// everything else counts as moved, so a single moved transaction is reported
+/// once rather than shifting every transaction after it.
+pub fn reordered_transactions(preconfirmed: &[String], canonical: &[String]) -> Vec<String> {
+    let canonical_positions: HashMap<&String, usize> = canonical
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(position, hash)| (hash, position))
+        .collect();
+    let common: Vec<(&String, usize)> = preconfirmed
+        .iter()
+        .filter_map(|hash| canonical_positions.get(hash).map(|&p| (hash, p)))
+        .collect();
+
+    // Hashes are unique, so the LCS of the two orderings is the longest
+    // increasing run of canonical positions.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; common.len()];
+    for (i, &(_, position)) in common.iter().enumerate() {
+        let len = tails.partition_point(|&t| common[t].1 < position);
+        previous[i] = len.checked_sub(1).map(|l| tails[l]);
+        if len == tails.len() {
+            tails.push(i);
+        } else {
+            tails[len] = i;
+        }
+    }
+
+    let mut in_order = vec![false; common.len()];
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        in_order[i] = true;
+        next = previous[i];
+    }
+
+    common
+        .iter()
+        .zip(in_order)
+        .filter(|(_, in_order)| !in_order)
+        .map(|((hash, _), _)| (*hash).clone())
+        .collect()
+}
+
+fn eq_ignore_case(a: Option<&str>, b: Option<&str>) -> bool {
+    matches!((a, b), (Some(a), Some(b)) if a.eq_ignore_ascii_case(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Map};
+
+    fn hashes(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn pending(transactions: &[&str]) -> PendingBlock {
+        PendingBlock {
+            payload_id: "0x01".to_string(),
+            block_number: 10,
+            base: None,
+            index: 3,
+            transactions: Vec::new(),
+            transaction_hashes: hashes(transactions),
+            receipts: Map::new(),
+            balances: Map::new(),
+            state_root: Some("0xaa".to_string()),
+            block_hash: Some("0xBB".to_string()),
+            gas_used: Some("0x5208".to_string()),
+        }
+    }
+
+    #[test]
+    fn identical_orderings_are_not_reordered() {
+        let txs = hashes(&["a", "b", "c"]);
+        assert!(reordered_transactions(&txs, &txs).is_empty());
+    }
+
+    #[test]
+    fn single_move_is_reported_once() {
+        let preconfirmed = hashes(&["a", "b", "c", "d", "e"]);
+        let canonical = hashes(&["b", "c", "d", "e", "a"]);
+        assert_eq!(
+            reordered_transactions(&preconfirmed, &canonical),
+            hashes(&["a"])
+        );
+    }
+
+    #[test]
+    fn reordering_ignores_missing_and_extra_transactions() {
+        let preconfirmed = hashes(&["a", "x", "b", "c"]);
+        let canonical = hashes(&["a", "c", "y", "b"]);
+        assert_eq!(
+            reordered_transactions(&preconfirmed, &canonical),
+            hashes(&["b"])
+        );
+    }
+
+    #[test]
+    fn compare_matches_identical_block() {
+        let block = pending(&["0x01", "0x02"]);
+        let canonical = json!({
+            "hash": "0xbb",
+            "stateRoot": "0xAA",
+            "gasUsed": "0x5208",
+            "transactions": ["0x01", "0x02"],
+        });
+
+        let report = compare(&block, &canonical);
+        assert!(report.is_match());
+        assert_eq!(report.canonical_transactions, 2);
+    }
+
+    #[test]
+    fn compare_reports_transaction_differences() {
+        let block = pending(&["0x01", "0x02", "0x03"]);
+        let canonical = json!({
+            "hash": "0xbb",
+            "stateRoot": "0xaa",
+            "gasUsed": "0x5208",
+            "transactions": [{ "hash": "0x03" }, { "hash": "0x01" }, { "hash": "0x04" }],
+        });
+
+        let report = compare(&block, &canonical);
+        assert_eq!(report.status, ReconcileStatus::Divergent);
+        assert_eq!(report.missing_transactions, hashes(&["0x02"]));
+        assert_eq!(report.extra_transactions, hashes(&["0x04"]));
+        assert_eq!(report.reordered_transactions, hashes(&["0x01"]));
+    }
+
+    #[test]
+    fn compare_detects_state_mismatch() {
+        let block = pending(&[]);
+        let canonical = json!({
+            "hash": "0xcc",
+            "stateRoot": "0xaa",
+            "gasUsed": "0x0",
+            "transactions": [],
+        });
+
+        let report = compare(&block, &canonical);
+        assert_eq!(report.status, ReconcileStatus::Divergent);
+        assert!(!report.block_hash_matches);
+        assert!(report.state_root_matches);
+        assert!(!report.gas_used_matches);
+    }
+}
@@ -1,22 +1,56 @@
+use crate::types::{hex_to_u128, hex_to_u64, JsonRpcRequest, JsonRpcResponse};
+use alloy::primitives::{Address, Bytes, U256};
 use eyre::Result;
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use std::error::Error;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
 
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockSummary {
+    pub number: u64,
+    pub hash: String,
+    pub timestamp: u64,
+    pub gas_used: u64,
+    pub transactions: usize,
+}
+
+#[derive(Clone)]
 pub struct FlashblocksRpcClient {
     endpoint: String,
+    client: reqwest::Client,
+    cancel: CancellationToken,
 }
 
 impl FlashblocksRpcClient {
     pub fn new(endpoint: String) -> Self {
-        Self { endpoint }
+        Self {
+            endpoint,
+            client: reqwest::Client::new(),
+            cancel: CancellationToken::new(),
+        }
     }
 
-    pub async fn query_latest_flashblock(&self) -> Result<(), Box<dyn Error>> {
-        info!("Testing eth_getBlockByNumber with 'pending' tag...");
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("a client that only sets a timeout builds wherever Client::new does");
+        self
+    }
+
+    /// Stops `monitor_blocks` and `monitor_blocks_with` once `cancel` is
+    /// cancelled.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
 
-        let client = reqwest::Client::new();
-        let response = client
+    pub async fn fetch_latest_flashblock(&self) -> Result<BlockSummary, Box<dyn Error>> {
+        let response = self
+            .client
             .post(&self.endpoint)
             .json(&serde_json::json!({
                 "jsonrpc": "2.0",
@@ -48,7 +82,7 @@ impl FlashblocksRpcClient {
             .transpose()?
             .unwrap_or(0);
 
-        let hash = block["hash"].as_str().unwrap_or("N/A");
+        let hash = block["hash"].as_str().unwrap_or("N/A").to_string();
         let transactions = block["transactions"]
             .as_array()
             .map(|t| t.len())
@@ -64,24 +98,43 @@ impl FlashblocksRpcClient {
             .transpose()?
             .unwrap_or(0);
 
+        Ok(BlockSummary {
+            number,
+            hash,
+            timestamp,
+            gas_used,
+            transactions,
+        })
+    }
+
+    pub async fn query_latest_flashblock(&self) -> Result<(), Box<dyn Error>> {
+        info!("Testing eth_getBlockByNumber with 'pending' tag...");
+
+        let block = self.fetch_latest_flashblock().await?;
+
         info!("Latest Flashblock:");
-        info!("  Number: {}", number);
-        info!("  Hash: {}", hash);
-        info!("  Timestamp: {}", timestamp);
-        info!("  Gas Used: {}", gas_used);
-        info!("  Transactions: {}", transactions);
+        info!("  Number: {}", block.number);
+        info!("  Hash: {}", block.hash);
+        info!("  Timestamp: {}", block.timestamp);
+        info!("  Gas Used: {}", block.gas_used);
+        info!("  Transactions: {}", block.transactions);
 
         Ok(())
     }
 
-    pub async fn get_balance(&self, address: &str) -> Result<u64, Box<dyn Error>> {
-        let client = reqwest::Client::new();
-        let response = client
+    /// Balance in wei at the latest block.
+    pub async fn get_balance(&self, address: &str) -> Result<U256, Box<dyn Error>> {
+        self.get_balance_at(address, "latest").await
+    }
+
+    pub async fn get_receipt(&self, tx_hash: &str) -> Result<Value, Box<dyn Error>> {
+        let response = self
+            .client
             .post(&self.endpoint)
             .json(&serde_json::json!({
                 "jsonrpc": "2.0",
-                "method": "eth_getBalance",
-                "params": [address, "latest"],
+                "method": "eth_getTransactionReceipt",
+                "params": [tx_hash],
                 "id": 1
             }))
             .send()
@@ -94,54 +147,140 @@ impl FlashblocksRpcClient {
             return Err("RPC error".into());
         }
 
-        let balance = json
+        let receipt = json
             .get("result")
-            .and_then(|v| v.as_str())
-            .map(|s| u64::from_str_radix(&s[2..], 16))
-            .transpose()?
-            .unwrap_or(0);
+            .ok_or_else(|| "No receipt found".to_string())?;
+
+        Ok(receipt.clone())
+    }
+
+    pub async fn get_chain_id(&self) -> Result<u64, Box<dyn Error>> {
+        let chain_id: String = self
+            .request("eth_chainId", serde_json::json!([]))
+            .await?
+            .ok_or("No chain id in response")?;
+        Ok(hex_to_u64(&chain_id).ok_or("Invalid chain id")?)
+    }
 
+    /// The node's suggested priority fee in wei.
+    pub async fn get_max_priority_fee_per_gas(&self) -> Result<u128, Box<dyn Error>> {
+        let fee: String = self
+            .request("eth_maxPriorityFeePerGas", serde_json::json!([]))
+            .await?
+            .ok_or("No priority fee in response")?;
+        Ok(hex_to_u128(&fee).ok_or("Invalid priority fee")?)
+    }
+
+    /// Balance in wei at `block` (a number or tag such as `"pending"`).
+    pub async fn get_balance_at(&self, address: &str, block: &str) -> Result<U256, Box<dyn Error>> {
+        let balance = self
+            .request("eth_getBalance", serde_json::json!([address, block]))
+            .await?
+            .ok_or("No balance in response")?;
         Ok(balance)
     }
 
-    pub async fn get_receipt(&self, tx_hash: &str) -> Result<Value, Box<dyn Error>> {
-        let client = reqwest::Client::new();
-        let response = client
+    /// Executes `eth_call` against the latest block.
+    pub async fn call(&self, to: Address, data: Bytes) -> Result<Bytes, Box<dyn Error>> {
+        let output = self
+            .request(
+                "eth_call",
+                serde_json::json!([{ "to": to, "data": data }, "latest"]),
+            )
+            .await?
+            .ok_or("No output in eth_call response")?;
+        Ok(output)
+    }
+
+    pub async fn get_block_by_number(
+        &self,
+        block: &str,
+        full_transactions: bool,
+    ) -> Result<Option<Value>, Box<dyn Error>> {
+        self.request(
+            "eth_getBlockByNumber",
+            serde_json::json!([block, full_transactions]),
+        )
+        .await
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<Option<T>, Box<dyn Error>> {
+        let response: JsonRpcResponse<T> = self
+            .client
             .post(&self.endpoint)
-            .json(&serde_json::json!({
-                "jsonrpc": "2.0",
-                "method": "eth_getTransactionReceipt",
-                "params": [tx_hash],
-                "id": 1
-            }))
+            .json(&JsonRpcRequest::new(method, params, 1))
             .send()
+            .await?
+            .json()
             .await?;
 
-        let json: Value = response.json().await?;
-
-        if let Some(error) = json.get("error") {
+        if let Some(error) = response.error {
             error!("RPC error: {:?}", error);
-            return Err("RPC error".into());
+            return Err(format!("RPC error {}: {}", error.code, error.message).into());
         }
 
-        let receipt = json
-            .get("result")
-            .ok_or_else(|| "No receipt found".to_string())?;
-
-        Ok(receipt.clone())
+        Ok(response.result)
     }
 
     pub async fn monitor_blocks(&self, count: u64) -> Result<(), Box<dyn Error>> {
         let mut blocks_seen = 0;
 
         while blocks_seen < count {
-            match self.query_latest_flashblock().await {
+            let result = tokio::select! {
+                result = self.query_latest_flashblock() => result,
+                _ = self.cancel.cancelled() => break,
+            };
+            match result {
                 Ok(_) => blocks_seen += 1,
                 Err(e) => error!("Error monitoring block: {}", e),
             }
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            if !self.wait_to_poll().await {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn monitor_blocks_with<F>(
+        &self,
+        count: u64,
+        mut on_block: F,
+    ) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(BlockSummary) -> Result<(), Box<dyn Error>>,
+    {
+        let mut blocks_seen = 0;
+
+        while blocks_seen < count {
+            let result = tokio::select! {
+                result = self.fetch_latest_flashblock() => result,
+                _ = self.cancel.cancelled() => break,
+            };
+            match result {
+                Ok(block) => {
+                    on_block(block)?;
+                    blocks_seen += 1;
+                }
+                Err(e) => error!("Error monitoring block: {}", e),
+            }
+            if !self.wait_to_poll().await {
+                break;
+            }
         }
 
         Ok(())
     }
+
+    /// Sleeps until the next poll; false when cancelled first.
+    async fn wait_to_poll(&self) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep(tokio::time::Duration::from_secs(1)) => true,
+            _ = self.cancel.cancelled() => false,
+        }
+    }
 }
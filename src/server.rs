@@ -0,0 +1,351 @@
+use crate::state::{PendingBlock, SharedPendingState};
+use crate::types::{hex_to_u128, hex_to_u64};
+use alloy::consensus::{Transaction, TxEnvelope};
+use alloy::eips::eip2718::Decodable2718;
+use alloy::primitives::{hex, Address, Bloom, B256};
+use axum::{extract::State, routing::post, Json, Router};
+use eyre::{Result, WrapErr};
+use op_alloy_consensus::{OpTxEnvelope, TxDeposit};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+use url::Url;
+
+/// JSON-RPC server answering pending-state queries from the flashblock feed
+/// and proxying everything else to an upstream node.
+#[derive(Clone)]
+pub struct PreconfServer {
+    state: SharedPendingState,
+    upstream: Url,
+    http: reqwest::Client,
+    cancel: CancellationToken,
+}
+
+impl PreconfServer {
+    pub fn new(state: SharedPendingState, upstream: Url) -> Self {
+        Self {
+            state,
+            upstream,
+            http: reqwest::Client::new(),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Stops accepting requests once `cancel` is cancelled and returns after
+    /// in-flight requests complete.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let cancel = self.cancel.clone();
+        let app = Router::new().route("/", post(handle)).with_state(self);
+        let listener = TcpListener::bind(addr)
+            .await
+            .wrap_err("Failed to bind JSON-RPC listener")?;
+        info!("Serving preconfirmed JSON-RPC on http://{}", addr);
+        axum::serve(listener, app)
+            .with_graceful_shutdown(cancel.cancelled_owned())
+            .await
+            .wrap_err("JSON-RPC server failed")?;
+        Ok(())
+    }
+
+    async fn dispatch(&self, request: Value) -> Value {
+        let method = request["method"].as_str().unwrap_or_default();
+        let params = &request["params"];
+
+        let local = {
+            let state = self.state.read().await;
+            state
+                .current()
+                .and_then(|block| answer_locally(block, method, params))
+        };
+
+        match local {
+            Some(result) => {
+                debug!("Answered {} from pending state", method);
+                json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+            }
+            None => self.proxy(&request).await,
+        }
+    }
+
+    async fn proxy(&self, request: &Value) -> Value {
+        let response = self
+            .http
+            .post(self.upstream.as_str())
+            .json(request)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) => match response.json::<Value>().await {
+                Ok(json) => json,
+                Err(e) => rpc_error(request, -32603, &format!("Invalid upstream response: {e}")),
+            },
+            Err(e) => {
+                error!("Upstream request failed: {}", e);
+                rpc_error(request, -32603, &format!("Upstream request failed: {e}"))
+            }
+        }
+    }
+}
+
+async fn handle(State(server): State<PreconfServer>, Json(body): Json<Value>) -> Json<Value> {
+    match body {
+        Value::Array(requests) => {
+            let mut responses = Vec::with_capacity(requests.len());
+            for request in requests {
+                responses.push(server.dispatch(request).await);
+            }
+            Json(Value::Array(responses))
+        }
+        request => Json(server.dispatch(request).await),
+    }
+}
+
+fn rpc_error(request: &Value, code: i32, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "error": { "code": code, "message": message }
+    })
+}
+
+fn answer_locally(block: &PendingBlock, method: &str, params: &Value) -> Option<Value> {
+    match method {
+        "eth_getBlockByNumber" if params[0].as_str() == Some("pending") => {
+            Some(block_json(block, params[1].as_bool().unwrap_or(false)))
+        }
+        "eth_getBalance" if params[1].as_str() == Some("pending") => {
+            block.balance(params[0].as_str()?).cloned()
+        }
+        "eth_getTransactionByHash" => {
+            let hash = params[0].as_str()?;
+            transaction_json(block, block.transaction_index(hash)?)
+        }
+        "eth_getTransactionReceipt" => {
+            let hash = params[0].as_str()?;
+            receipt_json(block, block.transaction_index(hash)?)
+        }
+        _ => None,
+    }
+}
+
+pub fn block_json(block: &PendingBlock, full_transactions: bool) -> Value {
+    let transactions: Vec<Value> = if full_transactions {
+        (0..block.transactions.len())
+            .filter_map(|index| transaction_json(block, index))
+            .collect()
+    } else {
+        block
+            .transaction_hashes
+            .iter()
+            .map(|hash| json!(hash))
+            .collect()
+    };
+
+    let mut json = json!({
+        "number": format!("{:#x}", block.block_number),
+        "hash": block.block_hash,
+        "stateRoot": block.state_root,
+        "gasUsed": block.gas_used.clone().unwrap_or_else(|| "0x0".to_string()),
+        "transactions": transactions,
+        "uncles": [],
+        "withdrawals": [],
+    });
+    if let Some(base) = &block.base {
+        json["parentHash"] = json!(base.parent_hash);
+        json["miner"] = json!(base.fee_recipient);
+        json["gasLimit"] = json!(base.gas_limit);
+        json["timestamp"] = json!(base.timestamp);
+        json["baseFeePerGas"] = json!(base.base_fee_per_gas);
+    }
+    json
+}
+
+/// Decodes a signed transaction. OP deposit transactions are not part of
+/// the L1 envelope; see [`decode_deposit`].
+pub fn decode_transaction(raw: &str) -> Option<TxEnvelope> {
+    let bytes = hex::decode(raw).ok()?;
+    TxEnvelope::decode_2718(&mut bytes.as_slice()).ok()
+}
+
+/// Decodes an OP deposit transaction (type `0x7e`).
+pub fn decode_deposit(raw: &str) -> Option<TxDeposit> {
+    let bytes = hex::decode(raw).ok()?;
+    match OpTxEnvelope::decode_2718(&mut bytes.as_slice()).ok()? {
+        OpTxEnvelope::Deposit(tx) => Some(tx.into_inner()),
+        _ => None,
+    }
+}
+
+pub fn transaction_json(block: &PendingBlock, index: usize) -> Option<Value> {
+    let raw = block.transactions.get(index)?;
+    let hash = &block.transaction_hashes[index];
+
+    let mut json = match decode_transaction(raw) {
+        Some(tx) => {
+            let mut json = serde_json::to_value(&tx).ok()?;
+            if let Ok(from) = tx.recover_signer() {
+                json["from"] = json!(from);
+            }
+            json
+        }
+        None => {
+            let deposit = decode_deposit(raw)?;
+            let mut json = serde_json::to_value(&deposit).ok()?;
+            json["type"] = json!("0x7e");
+            json["gasPrice"] = json!("0x0");
+            if let Some(nonce) = block.receipt(hash).and_then(|r| r.get("depositNonce")) {
+                json["nonce"] = nonce.clone();
+            }
+            json
+        }
+    };
+
+    json["hash"] = json!(hash);
+    json["blockHash"] = json!(block.block_hash);
+    json["blockNumber"] = json!(format!("{:#x}", block.block_number));
+    json["transactionIndex"] = json!(format!("{:#x}", index));
+    Some(json)
+}
+
+pub fn receipt_json(block: &PendingBlock, index: usize) -> Option<Value> {
+    let hash = &block.transaction_hashes[index];
+    let receipt = block.receipt(hash)?;
+
+    let cumulative = cumulative_gas_used(receipt);
+    let previous = block.transaction_hashes[..index]
+        .iter()
+        .rev()
+        .find_map(|h| block.receipt(h).map(cumulative_gas_used))
+        .unwrap_or(0);
+
+    let log_offset = block.log_offset(index);
+
+    let mut bloom = Bloom::default();
+    let logs: Vec<Value> = receipt["logs"]
+        .as_array()
+        .map(|logs| {
+            logs.iter()
+                .enumerate()
+                .map(|(i, log)| {
+                    accrue_log(&mut bloom, log);
+                    let mut log = log.clone();
+                    log["blockHash"] = json!(block.block_hash);
+                    log["blockNumber"] = json!(format!("{:#x}", block.block_number));
+                    log["transactionHash"] = json!(hash);
+                    log["transactionIndex"] = json!(format!("{:#x}", index));
+                    log["logIndex"] = json!(format!("{:#x}", log_offset + i));
+                    log["removed"] = json!(false);
+                    log
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut json = receipt.clone();
+    json["transactionHash"] = json!(hash);
+    json["transactionIndex"] = json!(format!("{:#x}", index));
+    json["blockHash"] = json!(block.block_hash);
+    json["blockNumber"] = json!(format!("{:#x}", block.block_number));
+    json["gasUsed"] = json!(format!("{:#x}", cumulative.saturating_sub(previous)));
+    json["logs"] = json!(logs);
+    json["logsBloom"] = json!(bloom);
+
+    let raw = &block.transactions[index];
+    if let Some(tx) = decode_transaction(raw) {
+        json["type"] = json!(format!("{:#x}", tx.tx_type() as u8));
+        json["to"] = json!(tx.to());
+        if let Ok(from) = tx.recover_signer() {
+            json["from"] = json!(from);
+        }
+        let base_fee = block
+            .base
+            .as_ref()
+            .and_then(|base| hex_to_u128(&base.base_fee_per_gas))
+            .unwrap_or_default();
+        json["effectiveGasPrice"] = json!(format!("{:#x}", effective_gas_price(&tx, base_fee)));
+        if tx.is_create() {
+            if let Ok(from) = tx.recover_signer() {
+                json["contractAddress"] = json!(from.create(tx.nonce()));
+            }
+        }
+    } else if let Some(deposit) = decode_deposit(raw) {
+        json["type"] = json!("0x7e");
+        json["from"] = json!(deposit.from);
+        json["to"] = json!(deposit.to.to());
+        json["effectiveGasPrice"] = json!("0x0");
+        let nonce = receipt["depositNonce"].as_str().and_then(hex_to_u64);
+        if let (true, Some(nonce)) = (deposit.to.is_create(), nonce) {
+            json["contractAddress"] = json!(deposit.from.create(nonce));
+        }
+    }
+
+    Some(json)
+}
+
+/// Gas price paid per unit, computed in `u128` so that large base fees are
+/// not truncated.
+fn effective_gas_price(tx: &TxEnvelope, base_fee: u128) -> u128 {
+    match tx.max_priority_fee_per_gas() {
+        Some(tip) => tx.max_fee_per_gas().min(base_fee.saturating_add(tip)),
+        None => tx.max_fee_per_gas(),
+    }
+}
+
+fn cumulative_gas_used(receipt: &Value) -> u64 {
+    receipt["cumulativeGasUsed"]
+        .as_str()
+        .and_then(hex_to_u64)
+        .unwrap_or_default()
+}
+
+fn accrue_log(bloom: &mut Bloom, log: &Value) {
+    let Some(address) = log["address"]
+        .as_str()
+        .and_then(|a| a.parse::<Address>().ok())
+    else {
+        return;
+    };
+    let topics: Vec<B256> = log["topics"]
+        .as_array()
+        .map(|topics| {
+            topics
+                .iter()
+                .filter_map(|t| t.as_str().and_then(|t| t.parse().ok()))
+                .collect()
+        })
+        .unwrap_or_default();
+    bloom.accrue_raw_log(address, &topics);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::transaction_type;
+    use alloy::eips::eip2718::Encodable2718;
+    use alloy::primitives::{TxKind, U256};
+    use op_alloy_consensus::TxDeposit;
+
+    #[test]
+    fn decodes_deposit_transactions() {
+        let deposit = TxDeposit {
+            from: Address::repeat_byte(0x11),
+            to: TxKind::Call(Address::repeat_byte(0x22)),
+            value: U256::from(1_000u64),
+            gas_limit: 100_000,
+            ..Default::default()
+        };
+        let raw = hex::encode_prefixed(deposit.encoded_2718());
+
+        assert!(decode_transaction(&raw).is_none());
+        assert_eq!(decode_deposit(&raw), Some(deposit));
+        assert_eq!(transaction_type(&raw), Some(0x7e));
+    }
+}
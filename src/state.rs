@@ -0,0 +1,230 @@
+use crate::types::{hex_to_u64, unwrap_receipt, Flashblock, FlashblockBase};
+use alloy::primitives::{hex, keccak256};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+/// A pending block assembled from the flashblocks of a single payload.
+#[derive(Debug, Clone)]
+pub struct PendingBlock {
+    pub payload_id: String,
+    pub block_number: u64,
+    pub base: Option<FlashblockBase>,
+    pub index: u64,
+    pub transactions: Vec<String>,
+    pub transaction_hashes: Vec<String>,
+    pub receipts: Map<String, Value>,
+    pub balances: Map<String, Value>,
+    pub state_root: Option<String>,
+    pub block_hash: Option<String>,
+    pub gas_used: Option<String>,
+}
+
+impl PendingBlock {
+    pub fn new(flashblock: &Flashblock) -> Self {
+        let mut block = Self {
+            payload_id: flashblock.payload_id.clone(),
+            block_number: flashblock.block_number().unwrap_or_default(),
+            base: flashblock.base.clone(),
+            index: flashblock.index,
+            transactions: Vec::new(),
+            transaction_hashes: Vec::new(),
+            receipts: Map::new(),
+            balances: Map::new(),
+            state_root: None,
+            block_hash: None,
+            gas_used: None,
+        };
+        block.apply(flashblock);
+        block
+    }
+
+    pub fn apply(&mut self, flashblock: &Flashblock) {
+        self.index = flashblock.index;
+        if self.base.is_none() {
+            self.base = flashblock.base.clone();
+        }
+        if self.block_number == 0 {
+            self.block_number = flashblock.block_number().unwrap_or_default();
+        }
+
+        let diff = &flashblock.diff;
+        if let Some(txs) = &diff.transactions {
+            for tx in txs {
+                let Some(hash) = transaction_hash(tx) else {
+                    warn!(
+                        "Skipping undecodable transaction in payload {}",
+                        self.payload_id
+                    );
+                    continue;
+                };
+                self.transaction_hashes.push(hash);
+                self.transactions.push(tx.clone());
+            }
+        }
+        if diff.state_root.is_some() {
+            self.state_root = diff.state_root.clone();
+        }
+        if diff.block_hash.is_some() {
+            self.block_hash = diff.block_hash.clone();
+        }
+        if diff.gas_used.is_some() {
+            self.gas_used = diff.gas_used.clone();
+        }
+
+        if let Some(receipts) = flashblock
+            .metadata
+            .receipts
+            .as_ref()
+            .and_then(|r| r.as_object())
+        {
+            for (hash, receipt) in receipts {
+                self.receipts.insert(hash.to_lowercase(), receipt.clone());
+            }
+        }
+        if let Some(balances) = flashblock
+            .metadata
+            .new_account_balances
+            .as_ref()
+            .and_then(|b| b.as_object())
+        {
+            for (address, balance) in balances {
+                self.balances
+                    .insert(address.to_lowercase(), balance.clone());
+            }
+        }
+    }
+
+    pub fn transaction_index(&self, hash: &str) -> Option<usize> {
+        let hash = hash.to_lowercase();
+        self.transaction_hashes.iter().position(|h| *h == hash)
+    }
+
+    pub fn raw_transaction(&self, hash: &str) -> Option<&str> {
+        self.transaction_index(hash)
+            .map(|index| self.transactions[index].as_str())
+    }
+
+    pub fn receipt(&self, hash: &str) -> Option<&Value> {
+        self.receipts.get(&hash.to_lowercase()).map(unwrap_receipt)
+    }
+
+    /// Number of logs emitted by the transactions before `index`, i.e. the
+    /// block-level index of the first log of transaction `index`.
+    pub fn log_offset(&self, index: usize) -> usize {
+        self.transaction_hashes[..index]
+            .iter()
+            .filter_map(|hash| self.receipt(hash))
+            .map(|receipt| receipt["logs"].as_array().map_or(0, Vec::len))
+            .sum()
+    }
+
+    pub fn balance(&self, address: &str) -> Option<&Value> {
+        self.balances.get(&address.to_lowercase())
+    }
+
+    pub fn gas_used(&self) -> Option<u64> {
+        self.gas_used.as_deref().and_then(hex_to_u64)
+    }
+}
+
+/// Hash of a hex-encoded raw transaction, or `None` if it is not valid hex.
+pub fn transaction_hash(raw: &str) -> Option<String> {
+    let bytes = hex::decode(raw).ok()?;
+    Some(format!("{:#x}", keccak256(bytes)))
+}
+
+/// Returns the EIP-2718 type of a raw transaction, `0` for legacy.
+pub fn transaction_type(raw: &str) -> Option<u8> {
+    let first = hex::decode(raw.get(..4)?).ok()?[0];
+    // Typed transactions start with a type byte up to 0x7f; legacy ones with
+    // an RLP list header from 0xc0.
+    Some(if first <= 0x7f { first } else { 0 })
+}
+
+fn is_stale(current: &PendingBlock, flashblock: &Flashblock) -> bool {
+    let older = flashblock
+        .block_number()
+        .is_some_and(|number| current.block_number > 0 && number < current.block_number);
+    older || (flashblock.index > 0 && flashblock.base.is_none())
+}
+
+/// Tracks the pending block currently being built by the sequencer.
+#[derive(Debug, Default)]
+pub struct PendingState {
+    current: Option<PendingBlock>,
+}
+
+pub type SharedPendingState = Arc<RwLock<PendingState>>;
+
+impl PendingState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn shared() -> SharedPendingState {
+        Arc::new(RwLock::new(Self::new()))
+    }
+
+    /// Applies a flashblock, returning the previous block if it was replaced
+    /// by a new payload. Flashblocks of an older block, or of a new payload
+    /// whose base was missed, are ignored.
+    pub fn apply(&mut self, flashblock: &Flashblock) -> Option<PendingBlock> {
+        match &mut self.current {
+            Some(block) if block.payload_id == flashblock.payload_id => {
+                block.apply(flashblock);
+                None
+            }
+            Some(block) if is_stale(block, flashblock) => {
+                debug!(
+                    "Ignoring flashblock {} of payload {} while building block {}",
+                    flashblock.index, flashblock.payload_id, block.block_number
+                );
+                None
+            }
+            _ => self.current.replace(PendingBlock::new(flashblock)),
+        }
+    }
+
+    pub fn current(&self) -> Option<&PendingBlock> {
+        self.current.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base(payload_id: &str, block_number: u64) -> Flashblock {
+        Flashblock::builder(payload_id, 0)
+            .with_base()
+            .with_block_number(block_number)
+            .build()
+    }
+
+    #[test]
+    fn ignores_stale_payloads() {
+        let mut state = PendingState::new();
+        assert!(state.apply(&base("0x02", 101)).is_none());
+
+        // An older block, and a later payload whose base was missed.
+        assert!(state.apply(&base("0x01", 100)).is_none());
+        let orphan = Flashblock::builder("0x03", 2)
+            .with_block_number(102)
+            .build();
+        assert!(state.apply(&orphan).is_none());
+        assert_eq!(state.current().unwrap().payload_id, "0x02");
+
+        let diff = Flashblock::builder("0x02", 1)
+            .with_transactions(["0x02"])
+            .build();
+        state.apply(&diff);
+        assert_eq!(state.current().unwrap().index, 1);
+
+        let replaced = state.apply(&base("0x03", 102)).unwrap();
+        assert_eq!(replaced.payload_id, "0x02");
+        assert_eq!(replaced.transactions, ["0x02"]);
+        assert_eq!(state.current().unwrap().block_number, 102);
+    }
+}
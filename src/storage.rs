@@ -0,0 +1,387 @@
+use crate::{
+    server::decode_transaction,
+    state::{transaction_hash, transaction_type},
+    types::{hex_to_u64, unwrap_receipt, Flashblock},
+};
+use alloy::consensus::Transaction;
+use chrono::{DateTime, Utc};
+use eyre::{Result, WrapErr};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::path::Path;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS bases (
+    payload_id TEXT PRIMARY KEY,
+    block_number INTEGER,
+    parent_hash TEXT NOT NULL,
+    fee_recipient TEXT NOT NULL,
+    gas_limit INTEGER,
+    timestamp INTEGER,
+    base_fee_per_gas TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS flashblocks (
+    payload_id TEXT NOT NULL,
+    idx INTEGER NOT NULL,
+    block_number INTEGER,
+    received_at INTEGER NOT NULL,
+    state_root TEXT,
+    block_hash TEXT,
+    gas_used INTEGER,
+    transaction_count INTEGER NOT NULL,
+    PRIMARY KEY (payload_id, idx)
+);
+CREATE INDEX IF NOT EXISTS flashblocks_block_number ON flashblocks (block_number);
+CREATE TABLE IF NOT EXISTS transactions (
+    hash TEXT NOT NULL,
+    payload_id TEXT NOT NULL,
+    block_number INTEGER,
+    flashblock_index INTEGER NOT NULL,
+    transaction_index INTEGER NOT NULL,
+    tx_type INTEGER,
+    sender TEXT,
+    recipient TEXT,
+    value TEXT,
+    nonce INTEGER,
+    raw TEXT NOT NULL,
+    PRIMARY KEY (hash, payload_id)
+);
+CREATE TABLE IF NOT EXISTS receipts (
+    transaction_hash TEXT NOT NULL,
+    payload_id TEXT NOT NULL,
+    status INTEGER,
+    cumulative_gas_used INTEGER,
+    receipt TEXT NOT NULL,
+    PRIMARY KEY (transaction_hash, payload_id)
+);
+";
+
+/// Where and when a transaction was first preconfirmed.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionHistory {
+    pub hash: String,
+    pub payload_id: String,
+    pub block_number: Option<u64>,
+    pub flashblock_index: u64,
+    pub transaction_index: u64,
+    pub first_seen_at: DateTime<Utc>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub value: Option<String>,
+    pub status: Option<u64>,
+}
+
+/// One flashblock in the timeline of a block.
+#[derive(Debug, Clone, Serialize)]
+pub struct FlashblockHistory {
+    pub block_number: u64,
+    pub payload_id: String,
+    pub index: u64,
+    pub received_at: DateTime<Utc>,
+    pub transactions: u64,
+    pub gas_used: Option<u64>,
+    pub block_hash: Option<String>,
+}
+
+/// Persists flashblocks to a SQLite database.
+pub struct SqliteStore {
+    conn: Connection,
+    payload_id: Option<String>,
+    transactions: u64,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .wrap_err_with(|| format!("Failed to open database {}", path.display()))?;
+        conn.execute_batch(SCHEMA)
+            .wrap_err("Failed to create database schema")?;
+        Ok(Self {
+            conn,
+            payload_id: None,
+            transactions: 0,
+        })
+    }
+
+    /// Writes the base, diff, decoded transactions and receipts of a
+    /// flashblock in a single database transaction.
+    pub fn record(&mut self, flashblock: &Flashblock, received_at: DateTime<Utc>) -> Result<()> {
+        if self.payload_id.as_deref() != Some(flashblock.payload_id.as_str()) {
+            self.payload_id = Some(flashblock.payload_id.clone());
+            self.transactions = 0;
+        }
+
+        let block_number = flashblock.block_number();
+        let db = self.conn.transaction()?;
+
+        if let Some(base) = &flashblock.base {
+            db.execute(
+                "INSERT OR REPLACE INTO bases VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    flashblock.payload_id,
+                    base.block_number(),
+                    base.parent_hash,
+                    base.fee_recipient,
+                    base.gas_limit(),
+                    base.timestamp(),
+                    base.base_fee_per_gas,
+                ],
+            )?;
+        }
+
+        db.execute(
+            "INSERT OR REPLACE INTO flashblocks VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                flashblock.payload_id,
+                flashblock.index,
+                block_number,
+                received_at.timestamp_millis(),
+                flashblock.diff.state_root,
+                flashblock.diff.block_hash,
+                flashblock.diff.gas_used(),
+                flashblock.transaction_count(),
+            ],
+        )?;
+
+        for raw in flashblock.diff.transactions.iter().flatten() {
+            let Some(hash) = transaction_hash(raw) else {
+                warn!(
+                    "Skipping undecodable transaction in payload {}",
+                    flashblock.payload_id
+                );
+                continue;
+            };
+            let tx = decode_transaction(raw);
+            db.execute(
+                "INSERT OR IGNORE INTO transactions VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    hash,
+                    flashblock.payload_id,
+                    block_number,
+                    flashblock.index,
+                    self.transactions,
+                    transaction_type(raw),
+                    tx.as_ref()
+                        .and_then(|tx| tx.recover_signer().ok())
+                        .map(|from| from.to_string()),
+                    tx.as_ref().and_then(|tx| tx.to()).map(|to| to.to_string()),
+                    tx.as_ref().map(|tx| tx.value().to_string()),
+                    tx.as_ref().map(|tx| tx.nonce()),
+                    raw,
+                ],
+            )?;
+            self.transactions += 1;
+
+            if let Some(receipt) = flashblock.metadata.receipt(&hash) {
+                let inner = unwrap_receipt(receipt);
+                db.execute(
+                    "INSERT OR REPLACE INTO receipts VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        hash,
+                        flashblock.payload_id,
+                        inner["status"].as_str().and_then(hex_to_u64),
+                        inner["cumulativeGasUsed"].as_str().and_then(hex_to_u64),
+                        receipt.to_string(),
+                    ],
+                )?;
+            }
+        }
+
+        db.commit()?;
+        Ok(())
+    }
+
+    /// Returns the earliest preconfirmation of a transaction.
+    pub fn transaction(&self, hash: &str) -> Result<Option<TransactionHistory>> {
+        self.conn
+            .query_row(
+                "SELECT t.hash, t.payload_id, t.block_number, t.flashblock_index,
+                        t.transaction_index, f.received_at, t.sender, t.recipient, t.value,
+                        r.status
+                 FROM transactions t
+                 JOIN flashblocks f ON f.payload_id = t.payload_id AND f.idx = t.flashblock_index
+                 LEFT JOIN receipts r ON r.transaction_hash = t.hash AND r.payload_id = t.payload_id
+                 WHERE t.hash = ?1
+                 ORDER BY f.received_at
+                 LIMIT 1",
+                params![hash.to_lowercase()],
+                |row| {
+                    Ok(TransactionHistory {
+                        hash: row.get(0)?,
+                        payload_id: row.get(1)?,
+                        block_number: row.get(2)?,
+                        flashblock_index: row.get(3)?,
+                        transaction_index: row.get(4)?,
+                        first_seen_at: millis_to_datetime(row.get(5)?),
+                        from: row.get(6)?,
+                        to: row.get(7)?,
+                        value: row.get(8)?,
+                        status: row.get(9)?,
+                    })
+                },
+            )
+            .optional()
+            .wrap_err("Failed to query transaction")
+    }
+
+    /// Returns every flashblock recorded for a block in the order received.
+    pub fn block(&self, block_number: u64) -> Result<Vec<FlashblockHistory>> {
+        let mut statement = self.conn.prepare(
+            "SELECT block_number, payload_id, idx, received_at, transaction_count, gas_used,
+                    block_hash
+             FROM flashblocks
+             WHERE block_number = ?1
+             ORDER BY received_at, idx",
+        )?;
+        let rows = statement.query_map(params![block_number], |row| {
+            Ok(FlashblockHistory {
+                block_number: row.get(0)?,
+                payload_id: row.get(1)?,
+                index: row.get(2)?,
+                received_at: millis_to_datetime(row.get(3)?),
+                transactions: row.get(4)?,
+                gas_used: row.get(5)?,
+                block_hash: row.get(6)?,
+            })
+        })?;
+        rows.collect::<Result<_, _>>()
+            .wrap_err("Failed to query block")
+    }
+
+    /// Records every flashblock received until the channel closes. SQLite
+    /// calls block, so this runs on the blocking thread pool.
+    pub async fn run(mut self, mut flashblocks: mpsc::UnboundedReceiver<Flashblock>) -> Result<()> {
+        tokio::task::spawn_blocking(move || {
+            let mut recorded = 0u64;
+            while let Some(flashblock) = flashblocks.blocking_recv() {
+                let received_at = flashblock
+                    .received
+                    .map_or_else(Utc::now, |received| received.wall_clock);
+                if let Err(e) = self.record(&flashblock, received_at) {
+                    error!("Failed to record flashblock: {:#}", e);
+                    continue;
+                }
+                recorded += 1;
+            }
+            info!("Recorded {} flashblocks", recorded);
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+fn millis_to_datetime(millis: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(millis).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        consensus::{SignableTransaction, TxEip1559, TxEnvelope},
+        eips::eip2718::Encodable2718,
+        primitives::{hex, Address, TxKind, U256},
+        signers::{local::PrivateKeySigner, SignerSync},
+    };
+    use serde_json::json;
+
+    fn transfer(signer: &PrivateKeySigner, to: Address) -> String {
+        let tx = TxEip1559 {
+            chain_id: 8453,
+            gas_limit: 21_000,
+            max_fee_per_gas: 2_000_000_000,
+            to: TxKind::Call(to),
+            value: U256::from(5),
+            ..Default::default()
+        };
+        let signature = signer.sign_hash_sync(&tx.signature_hash()).unwrap();
+        let envelope: TxEnvelope = tx.into_signed(signature).into();
+        hex::encode_prefixed(envelope.encoded_2718())
+    }
+
+    fn store() -> SqliteStore {
+        SqliteStore::open(Path::new(":memory:")).unwrap()
+    }
+
+    fn received(millis: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(millis).unwrap()
+    }
+
+    #[test]
+    fn records_bases_and_diffs_of_a_block() {
+        let mut store = store();
+        let base = Flashblock::builder("0x01", 0)
+            .with_base()
+            .with_gas_used(0)
+            .with_block_number(100)
+            .build();
+        let diff = Flashblock::builder("0x01", 1)
+            .with_gas_used(21_000)
+            .with_transactions(["0x02"])
+            .with_block_number(100)
+            .build();
+        store.record(&base, received(1_000)).unwrap();
+        store.record(&diff, received(1_200)).unwrap();
+
+        let (block_number, base_fee): (u64, String) = store
+            .conn
+            .query_row(
+                "SELECT block_number, base_fee_per_gas FROM bases WHERE payload_id = '0x01'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(block_number, 100);
+        assert_eq!(base_fee, "0x3b9aca00");
+
+        let timeline = store.block(100).unwrap();
+        assert_eq!(
+            timeline
+                .iter()
+                .map(|f| (f.index, f.transactions, f.gas_used))
+                .collect::<Vec<_>>(),
+            [(0, 0, Some(0)), (1, 1, Some(21_000))]
+        );
+        assert_eq!(timeline[1].received_at, received(1_200));
+        assert!(store.block(101).unwrap().is_empty());
+    }
+
+    #[test]
+    fn looks_up_transactions_with_their_receipts() {
+        let mut store = store();
+        let signer = PrivateKeySigner::random();
+        let to = Address::repeat_byte(0x11);
+        let raw = transfer(&signer, to);
+        let hash = transaction_hash(&raw).unwrap();
+        let flashblock = |payload_id| {
+            Flashblock::builder(payload_id, 1)
+                .with_transactions([raw.clone()])
+                .with_block_number(100)
+                .with_receipt(
+                    &hash,
+                    "Eip1559",
+                    json!({ "status": "0x1", "cumulativeGasUsed": "0x5208", "logs": [] }),
+                )
+                .build()
+        };
+        // A reorged payload includes the transaction again later.
+        store.record(&flashblock("0x02"), received(2_000)).unwrap();
+        store.record(&flashblock("0x01"), received(1_000)).unwrap();
+
+        let history = store.transaction(&hash.to_uppercase().replace("0X", "0x"));
+        let history = history.unwrap().unwrap();
+        assert_eq!(history.payload_id, "0x01");
+        assert_eq!(history.first_seen_at, received(1_000));
+        assert_eq!(
+            (history.flashblock_index, history.transaction_index),
+            (1, 0)
+        );
+        assert_eq!(history.from, Some(signer.address().to_string()));
+        assert_eq!(history.to, Some(to.to_string()));
+        assert_eq!(history.value.as_deref(), Some("5"));
+        assert_eq!(history.status, Some(1));
+        assert!(store.transaction("0x00").unwrap().is_none());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::fees::FeeEstimate;
+use crate::network::Network;
+use alloy::network::{EthereumWallet, TransactionBuilder};
+use alloy::primitives::{Address, U256};
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy::rpc::types::TransactionRequest;
+use alloy::signers::local::PrivateKeySigner;
+use eyre::{Result, WrapErr};
+use serde::Serialize;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::time;
+use tracing::info;
+use url::Url;
+
+pub struct TransferRequest {
+    pub private_key: String,
+    pub to: Option<Address>,
+    pub value: U256,
+    pub chain_id: Option<u64>,
+    /// Fees to pay; the provider's estimate is used when `None`
+    pub fees: Option<FeeEstimate>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SubmissionResult {
+    pub tx_hash: String,
+    pub preconfirmed_ms: u64,
+    pub block_number: Option<u64>,
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+}
+
+/// Probes the pending-state RPC methods exposed by a Flashblocks-aware node.
+pub async fn probe(rpc_url: Url) -> Result<()> {
+    let provider = ProviderBuilder::new().on_http(rpc_url);
+
+    let chain_id = provider.get_chain_id().await?;
+    let network = Network::from_chain_id(chain_id).map_or("unknown network", Network::display_name);
+    let latest_block = provider.get_block_number().await?;
+    info!(
+        "Connected to {} (chain id {}). Latest block: {}",
+        network, chain_id, latest_block
+    );
+
+    probe_pending_methods(&provider).await
+}
+
+/// Signs and sends a value transfer, then polls the preconfirmation endpoint
+/// until the receipt is visible.
+pub async fn submit_transfer(
+    rpc_url: Url,
+    request: TransferRequest,
+    timeout: Duration,
+) -> Result<SubmissionResult> {
+    let signer =
+        PrivateKeySigner::from_str(&request.private_key).wrap_err("Invalid private key")?;
+    let from = signer.address();
+    let provider = ProviderBuilder::new()
+        .wallet(EthereumWallet::from(signer))
+        .on_http(rpc_url);
+
+    let mut tx = TransactionRequest::default()
+        .with_to(request.to.unwrap_or(from))
+        .with_value(request.value);
+    if let Some(chain_id) = request.chain_id {
+        tx = tx.with_chain_id(chain_id);
+    }
+    if let Some(fees) = &request.fees {
+        info!(
+            "Using max fee {} wei and priority fee {} wei for inclusion within {} flashblocks",
+            fees.max_fee_per_gas, fees.max_priority_fee_per_gas, fees.target_flashblocks
+        );
+        tx = tx
+            .with_max_fee_per_gas(fees.max_fee_per_gas)
+            .with_max_priority_fee_per_gas(fees.max_priority_fee_per_gas);
+    }
+
+    let started = Instant::now();
+    let pending = provider
+        .send_transaction(tx)
+        .await
+        .wrap_err("Failed to send transaction")?;
+    let tx_hash = *pending.tx_hash();
+    info!("Submitted transaction {}", tx_hash);
+
+    let deadline = started + timeout;
+    while Instant::now() < deadline {
+        if let Some(receipt) = provider.get_transaction_receipt(tx_hash).await? {
+            let preconfirmed_after = started.elapsed();
+            info!(
+                "Transaction {} preconfirmed after {}ms",
+                tx_hash,
+                preconfirmed_after.as_millis()
+            );
+            return Ok(SubmissionResult {
+                tx_hash: format!("{:#x}", tx_hash),
+                preconfirmed_ms: preconfirmed_after.as_millis() as u64,
+                block_number: receipt.block_number,
+                max_fee_per_gas: request.fees.as_ref().map(|fees| fees.max_fee_per_gas),
+                max_priority_fee_per_gas: request
+                    .fees
+                    .as_ref()
+                    .map(|fees| fees.max_priority_fee_per_gas),
+            });
+        }
+        time::sleep(Duration::from_millis(50)).await;
+    }
+
+    Err(eyre::eyre!(
+        "Transaction {:#x} was not preconfirmed within {:?}",
+        tx_hash,
+        timeout
+    ))
+}
+
+pub async fn probe_pending_methods(provider: &impl Provider) -> Result<()> {
+    info!("Testing eth_getBlockByNumber with 'pending' tag...");
+    let method = "eth_getBlockByNumber";
+    let pending_block: Option<serde_json::Value> = provider
+        .raw_request(method.into(), serde_json::json!(["pending", true]))
+        .await?;
+
+    if let Some(block) = pending_block {
+        info!("Latest Flashblock:");
+        info!(
+            "  Number: {}",
+            block.get("number").unwrap_or(&serde_json::json!("unknown"))
+        );
+        info!(
+            "  Hash: {}",
+            block.get("hash").unwrap_or(&serde_json::json!("unknown"))
+        );
+        if let Some(txs) = block.get("transactions").and_then(|t| t.as_array()) {
+            info!("  Transaction count: {}", txs.len());
+        }
+    }
+
+    let test_address = Address::from_str("0x4200000000000000000000000000000000000011")?;
+    info!("\nTesting eth_getBalance with 'pending' tag...");
+    let method = "eth_getBalance";
+    let balance: serde_json::Value = provider
+        .raw_request(
+            method.into(),
+            serde_json::json!([format!("{test_address:?}"), "pending"]),
+        )
+        .await?;
+
+    info!("Balance for {test_address:?}:");
+    info!("  Raw: {}", balance);
+
+    info!("\nMonitoring for new blocks for 30 seconds...");
+    let start_block = provider.get_block_number().await?;
+    let end_time = tokio::time::Instant::now() + Duration::from_secs(30);
+
+    while tokio::time::Instant::now() < end_time {
+        let current_block = provider.get_block_number().await?;
+        if current_block > start_block {
+            info!("New block detected: {}", current_block);
+
+            let block: Option<serde_json::Value> = provider
+                .raw_request(
+                    "eth_getBlockByNumber".into(),
+                    serde_json::json!([format!("0x{:x}", current_block), true]),
+                )
+                .await?;
+
+            if let Some(block) = block {
+                info!(
+                    "  Timestamp: {}",
+                    block
+                        .get("timestamp")
+                        .unwrap_or(&serde_json::json!("unknown"))
+                );
+                info!(
+                    "  Gas Used: {}",
+                    block
+                        .get("gasUsed")
+                        .unwrap_or(&serde_json::json!("unknown"))
+                );
+                if let Some(txs) = block.get("transactions").and_then(|t| t.as_array()) {
+                    info!("  Transactions: {}", txs.len());
+                }
+            }
+        }
+        time::sleep(Duration::from_millis(250)).await;
+    }
+
+    Ok(())
+}
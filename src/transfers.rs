@@ -0,0 +1,375 @@
+use crate::{
+    logs::{FlashblockLog, LogSubscription},
+    rpc::FlashblocksRpcClient,
+    types::Flashblock,
+};
+use alloy::{
+    primitives::{utils::format_units, Address, FixedBytes, B256, U256},
+    rpc::types::Filter,
+    sol_types::{SolCall, SolEvent},
+};
+use erc20::{decimalsCall, symbolCall, Transfer};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+mod erc20 {
+    alloy::sol! {
+        event Transfer(address indexed from, address indexed to, uint256 value);
+
+        function symbol() external view returns (string);
+        function decimals() external view returns (uint8);
+    }
+}
+
+/// Legacy tokens such as MKR return `bytes32` from `symbol()`.
+mod legacy {
+    alloy::sol! {
+        function symbol() external view returns (bytes32);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenInfo {
+    pub address: Address,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+}
+
+/// A preconfirmed ERC-20 transfer.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenTransfer {
+    pub block_number: Option<u64>,
+    pub flashblock_index: u64,
+    pub transaction_hash: B256,
+    pub log_index: u64,
+    pub token: TokenInfo,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+}
+
+impl TokenTransfer {
+    /// The transferred amount scaled by the token's decimals, or the raw
+    /// value if they are unknown.
+    pub fn amount(&self) -> String {
+        self.token
+            .decimals
+            .and_then(|decimals| format_units(self.value, decimals).ok())
+            .unwrap_or_else(|| self.value.to_string())
+    }
+}
+
+/// Token metadata and, if any of it is missing, when to look it up again.
+struct CachedToken {
+    info: TokenInfo,
+    retry_at: Option<Instant>,
+}
+
+/// Decodes ERC-20 `Transfer` logs from flashblock receipts and resolves the
+/// token metadata of each transfer through cached `eth_call`s.
+pub struct TransferTracker {
+    rpc: FlashblocksRpcClient,
+    subscription: LogSubscription,
+    wallets: HashSet<Address>,
+    tokens: HashMap<Address, CachedToken>,
+    retry: Duration,
+}
+
+impl TransferTracker {
+    pub fn new(rpc: FlashblocksRpcClient) -> Self {
+        Self {
+            rpc,
+            subscription: LogSubscription::new(Self::filter(Vec::new())),
+            wallets: HashSet::new(),
+            tokens: HashMap::new(),
+            retry: Duration::from_secs(60),
+        }
+    }
+
+    /// Only tracks transfers of the given tokens.
+    pub fn with_tokens(mut self, tokens: Vec<Address>) -> Self {
+        self.subscription = LogSubscription::new(Self::filter(tokens));
+        self
+    }
+
+    /// Only tracks transfers sent or received by the given wallets.
+    pub fn with_wallets(mut self, wallets: impl IntoIterator<Item = Address>) -> Self {
+        self.wallets = wallets.into_iter().collect();
+        self
+    }
+
+    /// How long incomplete token metadata, e.g. after a failed `eth_call`,
+    /// is used before it is looked up again.
+    pub fn with_metadata_retry(mut self, retry: Duration) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    fn filter(tokens: Vec<Address>) -> Filter {
+        Filter::new()
+            .address(tokens)
+            .event_signature(Transfer::SIGNATURE_HASH)
+    }
+
+    /// Returns the transfers in a flashblock that match the token and wallet
+    /// filters.
+    pub async fn observe(&mut self, flashblock: &Flashblock) -> Vec<TokenTransfer> {
+        let mut transfers = Vec::new();
+        for log in self.subscription.observe(flashblock) {
+            // ERC-721 transfers share the signature but index the token id,
+            // so they fail to decode here.
+            let Some(event) = log.decode::<Transfer>() else {
+                continue;
+            };
+            if !self.wallets.is_empty()
+                && !self.wallets.contains(&event.from)
+                && !self.wallets.contains(&event.to)
+            {
+                continue;
+            }
+            let token = self.token(log.log.address).await;
+            transfers.push(transfer(&log, token, event));
+        }
+        transfers
+    }
+
+    /// Returns the metadata of a token, fetching it on first use and again
+    /// after the retry interval while any of it is missing.
+    pub async fn token(&mut self, address: Address) -> TokenInfo {
+        if let Some(token) = self.tokens.get(&address) {
+            if token
+                .retry_at
+                .is_none_or(|retry_at| Instant::now() < retry_at)
+            {
+                return token.info.clone();
+            }
+        }
+
+        let symbol = self.symbol(address).await;
+        let decimals = self
+            .rpc
+            .call(address, decimalsCall {}.abi_encode().into())
+            .await
+            .ok()
+            .and_then(|output| decimalsCall::abi_decode_returns(&output, false).ok())
+            .map(|decimals| decimals._0);
+        debug!("Resolved token {}: {:?} ({:?})", address, symbol, decimals);
+
+        let token = TokenInfo {
+            address,
+            symbol,
+            decimals,
+        };
+        let complete = token.symbol.is_some() && token.decimals.is_some();
+        self.tokens.insert(
+            address,
+            CachedToken {
+                info: token.clone(),
+                retry_at: (!complete).then(|| Instant::now() + self.retry),
+            },
+        );
+        token
+    }
+
+    async fn symbol(&self, address: Address) -> Option<String> {
+        let output = self
+            .rpc
+            .call(address, symbolCall {}.abi_encode().into())
+            .await
+            .ok()?;
+        if let Ok(symbol) = symbolCall::abi_decode_returns(&output, false) {
+            return Some(symbol._0);
+        }
+        legacy::symbolCall::abi_decode_returns(&output, false)
+            .ok()
+            .map(|symbol| bytes32_to_string(symbol._0))
+    }
+
+    /// Consumes flashblocks and sends every matching transfer.
+    pub async fn run(
+        mut self,
+        mut flashblocks: mpsc::UnboundedReceiver<Flashblock>,
+        transfers: mpsc::UnboundedSender<TokenTransfer>,
+    ) {
+        while let Some(flashblock) = flashblocks.recv().await {
+            for transfer in self.observe(&flashblock).await {
+                if transfers.send(transfer).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn transfer(log: &FlashblockLog, token: TokenInfo, event: Transfer) -> TokenTransfer {
+    TokenTransfer {
+        block_number: log.block_number,
+        flashblock_index: log.flashblock_index,
+        transaction_hash: log.transaction_hash,
+        log_index: log.log_index,
+        token,
+        from: event.from,
+        to: event.to,
+        value: event.value,
+    }
+}
+
+fn bytes32_to_string(bytes: FixedBytes<32>) -> String {
+    String::from_utf8_lossy(bytes.as_slice())
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::transaction_hash;
+    use alloy::primitives::{hex, LogData};
+    use axum::{extract::State, routing::post, Json, Router};
+    use serde_json::{json, Value};
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    };
+
+    const TOKEN: Address = Address::repeat_byte(0x01);
+    const OTHER_TOKEN: Address = Address::repeat_byte(0x02);
+    const WALLET: Address = Address::repeat_byte(0xaa);
+    const OTHER: Address = Address::repeat_byte(0xbb);
+
+    /// Serves `symbol()` and `decimals()` of every token, or errors while
+    /// `failing` is set.
+    async fn token_rpc(failing: Arc<AtomicBool>) -> FlashblocksRpcClient {
+        async fn handle(
+            State(failing): State<Arc<AtomicBool>>,
+            Json(request): Json<Value>,
+        ) -> Json<Value> {
+            if failing.load(Ordering::SeqCst) {
+                return Json(json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": { "code": -32000, "message": "header not found" },
+                }));
+            }
+            let data = request["params"][0]["data"].as_str().unwrap_or_default();
+            let output = if data.starts_with(&hex::encode_prefixed(symbolCall::SELECTOR)) {
+                symbolCall::abi_encode_returns(&("USDC".to_string(),))
+            } else {
+                decimalsCall::abi_encode_returns(&(6u8,))
+            };
+            Json(json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "result": hex::encode_prefixed(output),
+            }))
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().route("/", post(handle)).with_state(failing);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        FlashblocksRpcClient::new(endpoint)
+    }
+
+    fn log(token: Address, data: LogData) -> Value {
+        json!({ "address": token, "topics": data.topics(), "data": data.data })
+    }
+
+    fn erc20(token: Address, from: Address, to: Address, value: u64) -> Value {
+        let event = Transfer {
+            from,
+            to,
+            value: U256::from(value),
+        };
+        log(token, event.encode_log_data())
+    }
+
+    fn flashblock(logs: Vec<Value>) -> Flashblock {
+        Flashblock::builder("0x01", 1)
+            .with_block_number(100)
+            .with_transactions(["0x01"])
+            .with_receipt(
+                &transaction_hash("0x01").unwrap(),
+                "Eip1559",
+                json!({ "status": "0x1", "logs": logs }),
+            )
+            .build()
+    }
+
+    #[tokio::test]
+    async fn decodes_transfers_matching_the_filters() {
+        let rpc = token_rpc(Arc::default()).await;
+        // An ERC-721 transfer indexes the token id as a fourth topic.
+        let nft = LogData::new_unchecked(
+            vec![
+                Transfer::SIGNATURE_HASH,
+                WALLET.into_word(),
+                OTHER.into_word(),
+                B256::with_last_byte(1),
+            ],
+            Default::default(),
+        );
+        let flashblock = flashblock(vec![
+            erc20(TOKEN, WALLET, OTHER, 1_500_000),
+            erc20(OTHER_TOKEN, WALLET, OTHER, 1),
+            erc20(TOKEN, OTHER, OTHER, 2),
+            log(TOKEN, nft),
+        ]);
+
+        let all = TransferTracker::new(rpc.clone()).observe(&flashblock).await;
+        assert_eq!(all.len(), 3);
+        assert_eq!(
+            (all[0].from, all[0].to, all[0].value),
+            (WALLET, OTHER, U256::from(1_500_000))
+        );
+        assert_eq!(all[0].token.symbol.as_deref(), Some("USDC"));
+        assert_eq!(all[0].amount(), "1.500000");
+        assert_eq!((all[0].block_number, all[0].log_index), (Some(100), 0));
+
+        let token = TransferTracker::new(rpc.clone())
+            .with_tokens(vec![TOKEN])
+            .observe(&flashblock)
+            .await;
+        assert_eq!(
+            token.iter().map(|t| t.log_index).collect::<Vec<_>>(),
+            [0, 2]
+        );
+
+        let wallet = TransferTracker::new(rpc)
+            .with_wallets([WALLET])
+            .observe(&flashblock)
+            .await;
+        assert_eq!(
+            wallet.iter().map(|t| t.log_index).collect::<Vec<_>>(),
+            [0, 1]
+        );
+    }
+
+    #[tokio::test]
+    async fn retries_failed_metadata_lookups() {
+        let failing = Arc::new(AtomicBool::new(true));
+        let rpc = token_rpc(failing.clone()).await;
+
+        let mut cached = TransferTracker::new(rpc.clone());
+        let mut retrying = TransferTracker::new(rpc).with_metadata_retry(Duration::ZERO);
+        for tracker in [&mut cached, &mut retrying] {
+            let token = tracker.token(TOKEN).await;
+            assert_eq!((token.symbol, token.decimals), (None, None));
+        }
+
+        failing.store(false, Ordering::SeqCst);
+        assert_eq!(cached.token(TOKEN).await.symbol, None);
+        let token = retrying.token(TOKEN).await;
+        assert_eq!(token.symbol.as_deref(), Some("USDC"));
+        assert_eq!(token.decimals, Some(6));
+
+        // Complete metadata is kept.
+        failing.store(true, Ordering::SeqCst);
+        assert_eq!(retrying.token(TOKEN).await.decimals, Some(6));
+    }
+}
@@ -0,0 +1,114 @@
+use super::{hex_to_u64, Flashblock, FlashblockBase, FlashblockDiff, FlashblockMetadata};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::value::RawValue;
+
+/// Borrowed view of [`FlashblockBase`]. Hex fields are never escaped,
+/// so they borrow straight from the message.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlashblockBaseRef<'a> {
+    pub parent_hash: &'a str,
+    pub fee_recipient: &'a str,
+    pub block_number: &'a str,
+    pub gas_limit: &'a str,
+    pub timestamp: &'a str,
+    pub base_fee_per_gas: &'a str,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlashblockDiffRef<'a> {
+    #[serde(borrow)]
+    pub state_root: Option<&'a str>,
+    #[serde(borrow)]
+    pub block_hash: Option<&'a str>,
+    #[serde(borrow)]
+    pub gas_used: Option<&'a str>,
+    #[serde(borrow)]
+    pub transactions: Option<Vec<&'a str>>,
+    #[serde(borrow)]
+    pub withdrawals: Option<&'a RawValue>,
+}
+
+/// Balances and receipts are kept as raw JSON and only parsed on demand.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlashblockMetadataRef<'a> {
+    pub block_number: Option<u64>,
+    #[serde(borrow)]
+    pub new_account_balances: Option<&'a RawValue>,
+    #[serde(borrow)]
+    pub receipts: Option<&'a RawValue>,
+}
+
+/// A flashblock borrowing its strings from the message it was parsed from,
+/// for consumers that only inspect a few fields of each message.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlashblockRef<'a> {
+    pub payload_id: &'a str,
+    pub index: u64,
+    #[serde(borrow, default)]
+    pub base: Option<FlashblockBaseRef<'a>>,
+    #[serde(borrow)]
+    pub diff: FlashblockDiffRef<'a>,
+    #[serde(borrow)]
+    pub metadata: FlashblockMetadataRef<'a>,
+}
+
+impl<'a> FlashblockRef<'a> {
+    pub fn parse(message: &'a str) -> serde_json::Result<Self> {
+        serde_json::from_str(message)
+    }
+
+    pub fn block_number(&self) -> Option<u64> {
+        self.metadata.block_number.or_else(|| {
+            self.base
+                .as_ref()
+                .and_then(|base| hex_to_u64(base.block_number))
+        })
+    }
+
+    pub fn transaction_count(&self) -> usize {
+        self.diff.transactions.as_ref().map_or(0, |txs| txs.len())
+    }
+
+    pub fn is_initial(&self) -> bool {
+        self.index == 0
+    }
+
+    /// Copies the flashblock into its owned form, parsing the raw metadata.
+    pub fn to_flashblock(&self) -> serde_json::Result<Flashblock> {
+        Ok(Flashblock {
+            payload_id: self.payload_id.to_string(),
+            index: self.index,
+            base: self.base.as_ref().map(|base| FlashblockBase {
+                parent_hash: base.parent_hash.to_string(),
+                fee_recipient: base.fee_recipient.to_string(),
+                block_number: base.block_number.to_string(),
+                gas_limit: base.gas_limit.to_string(),
+                timestamp: base.timestamp.to_string(),
+                base_fee_per_gas: base.base_fee_per_gas.to_string(),
+            }),
+            diff: FlashblockDiff {
+                state_root: self.diff.state_root.map(str::to_string),
+                block_hash: self.diff.block_hash.map(str::to_string),
+                gas_used: self.diff.gas_used.map(str::to_string),
+                transactions: self
+                    .diff
+                    .transactions
+                    .as_ref()
+                    .map(|txs| txs.iter().map(|tx| tx.to_string()).collect()),
+                withdrawals: parse_raw(self.diff.withdrawals)?,
+            },
+            metadata: FlashblockMetadata {
+                block_number: self.metadata.block_number,
+                new_account_balances: parse_raw(self.metadata.new_account_balances)?,
+                receipts: parse_raw(self.metadata.receipts)?,
+            },
+            received: None,
+        })
+    }
+}
+
+fn parse_raw<T: DeserializeOwned>(value: Option<&RawValue>) -> serde_json::Result<Option<T>> {
+    value
+        .map(|value| serde_json::from_str(value.get()))
+        .transpose()
+}
@@ -1,5 +1,8 @@
+use alloy::primitives::{Address, Bytes, Log, B256};
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use serde_json::Value;
+use std::{str::FromStr, time::Instant};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct FlashblockBase {
@@ -27,6 +30,21 @@ pub struct FlashblockMetadata {
     pub receipts: Option<Value>,
 }
 
+impl FlashblockMetadata {
+    /// Looks up the receipt of a transaction, ignoring the case of the hash
+    /// the builder keyed it by. The receipt is still wrapped in its type; see
+    /// [`unwrap_receipt`].
+    pub fn receipt(&self, hash: &str) -> Option<&Value> {
+        let receipts = self.receipts.as_ref()?.as_object()?;
+        receipts.get(hash).or_else(|| {
+            receipts
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(hash))
+                .map(|(_, receipt)| receipt)
+        })
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Flashblock {
     pub payload_id: String,
@@ -35,17 +53,90 @@ pub struct Flashblock {
     pub base: Option<FlashblockBase>,
     pub diff: FlashblockDiff,
     pub metadata: FlashblockMetadata,
+    /// When the message carrying this flashblock was read from the socket.
+    #[serde(skip)]
+    pub received: Option<ReceiveTime>,
+}
+
+/// Receive time of a message, on both the monotonic and the wall clock.
+#[derive(Debug, Clone, Copy)]
+pub struct ReceiveTime {
+    pub monotonic: Instant,
+    pub wall_clock: DateTime<Utc>,
+}
+
+impl ReceiveTime {
+    pub fn now() -> Self {
+        Self {
+            monotonic: Instant::now(),
+            wall_clock: Utc::now(),
+        }
+    }
+}
+
+pub fn hex_to_u64(hex: &str) -> Option<u64> {
+    hex.strip_prefix("0x")
+        .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+}
+
+pub fn hex_to_u128(hex: &str) -> Option<u128> {
+    hex.strip_prefix("0x")
+        .and_then(|hex| u128::from_str_radix(hex, 16).ok())
+}
+
+/// Receipts are keyed by transaction hash and wrapped in their transaction
+/// type (e.g. `{"Eip1559": {...}}`); this returns the inner receipt object.
+pub fn unwrap_receipt(receipt: &Value) -> &Value {
+    match receipt.as_object() {
+        Some(obj) if obj.len() == 1 => match obj.values().next() {
+            Some(inner) if inner.is_object() => inner,
+            _ => receipt,
+        },
+        _ => receipt,
+    }
+}
+
+/// Parses a log object from a flashblock receipt.
+pub fn parse_log(log: &Value) -> Option<Log> {
+    let address = Address::from_str(log["address"].as_str()?).ok()?;
+    let topics = log["topics"]
+        .as_array()?
+        .iter()
+        .map(|topic| B256::from_str(topic.as_str()?).ok())
+        .collect::<Option<Vec<_>>>()?;
+    let data = Bytes::from_str(log["data"].as_str().unwrap_or("0x")).ok()?;
+    Log::new(address, topics, data)
+}
+
+impl FlashblockBase {
+    pub fn block_number(&self) -> Option<u64> {
+        hex_to_u64(&self.block_number)
+    }
+
+    pub fn timestamp(&self) -> Option<u64> {
+        hex_to_u64(&self.timestamp)
+    }
+
+    pub fn gas_limit(&self) -> Option<u64> {
+        hex_to_u64(&self.gas_limit)
+    }
+
+    pub fn base_fee_per_gas(&self) -> Option<u128> {
+        hex_to_u128(&self.base_fee_per_gas)
+    }
+}
+
+impl FlashblockDiff {
+    pub fn gas_used(&self) -> Option<u64> {
+        self.gas_used.as_deref().and_then(hex_to_u64)
+    }
 }
 
 impl Flashblock {
     pub fn block_number(&self) -> Option<u64> {
-        self.metadata.block_number.or_else(|| {
-            self.base.as_ref().and_then(|base| {
-                base.block_number
-                    .strip_prefix("0x")
-                    .and_then(|hex| u64::from_str_radix(hex, 16).ok())
-            })
-        })
+        self.metadata
+            .block_number
+            .or_else(|| self.base.as_ref().and_then(|base| base.block_number()))
     }
 
     pub fn transaction_count(&self) -> usize {
@@ -56,3 +147,111 @@ impl Flashblock {
         self.index == 0
     }
 }
+
+/// Builds flashblocks for tests from the JSON the feed sends.
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub(crate) struct FlashblockBuilder {
+    json: Value,
+    received: Option<ReceiveTime>,
+}
+
+#[cfg(test)]
+impl Flashblock {
+    /// A flashblock with an empty diff and no base or metadata.
+    pub(crate) fn builder(payload_id: &str, index: u64) -> FlashblockBuilder {
+        FlashblockBuilder {
+            json: serde_json::json!({
+                "payload_id": payload_id,
+                "index": index,
+                "diff": {},
+                "metadata": {},
+            }),
+            received: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl FlashblockBuilder {
+    pub(crate) const BLOCK_NUMBER: u64 = 100;
+    pub(crate) const GAS_LIMIT: u64 = 30_000_000;
+    pub(crate) const BASE_FEE: u128 = 1_000_000_000;
+
+    /// Adds a base for block [`Self::BLOCK_NUMBER`] with a
+    /// [`Self::GAS_LIMIT`] gas limit, a [`Self::BASE_FEE`] base fee and
+    /// timestamp 0.
+    pub(crate) fn with_base(mut self) -> Self {
+        if self.json["base"].is_null() {
+            self.json["base"] = serde_json::json!({
+                "parent_hash": "0x00",
+                "fee_recipient": "0x00",
+                "block_number": format!("{:#x}", Self::BLOCK_NUMBER),
+                "gas_limit": format!("{:#x}", Self::GAS_LIMIT),
+                "timestamp": "0x0",
+                "base_fee_per_gas": format!("{:#x}", Self::BASE_FEE),
+            });
+        }
+        self
+    }
+
+    pub(crate) fn with_timestamp(self, timestamp: u64) -> Self {
+        let mut builder = self.with_base();
+        builder.json["base"]["timestamp"] = format!("{timestamp:#x}").into();
+        builder
+    }
+
+    pub(crate) fn with_base_fee(self, base_fee: u128) -> Self {
+        let mut builder = self.with_base();
+        builder.json["base"]["base_fee_per_gas"] = format!("{base_fee:#x}").into();
+        builder
+    }
+
+    pub(crate) fn with_block_number(mut self, block_number: u64) -> Self {
+        self.json["metadata"]["block_number"] = block_number.into();
+        self
+    }
+
+    pub(crate) fn with_gas_used(mut self, gas_used: u64) -> Self {
+        self.json["diff"]["gas_used"] = format!("{gas_used:#x}").into();
+        self
+    }
+
+    pub(crate) fn with_transactions<S: Into<String>>(
+        mut self,
+        transactions: impl IntoIterator<Item = S>,
+    ) -> Self {
+        let transactions: Vec<String> = transactions.into_iter().map(Into::into).collect();
+        self.json["diff"]["transactions"] = transactions.into();
+        self
+    }
+
+    /// Adds a receipt keyed by `hash` as given, wrapped in `tx_type`
+    /// (e.g. `"Eip1559"`).
+    pub(crate) fn with_receipt(mut self, hash: &str, tx_type: &str, receipt: Value) -> Self {
+        self.json["metadata"]["receipts"][hash] = serde_json::json!({ tx_type: receipt });
+        self
+    }
+
+    pub(crate) fn with_balance(mut self, address: &str, balance: impl Into<Value>) -> Self {
+        self.json["metadata"]["new_account_balances"][address] = balance.into();
+        self
+    }
+
+    pub(crate) fn with_received(mut self, received: ReceiveTime) -> Self {
+        self.received = Some(received);
+        self
+    }
+
+    /// The message as the feed would send it.
+    pub(crate) fn json(&self) -> Value {
+        self.json.clone()
+    }
+
+    pub(crate) fn build(self) -> Flashblock {
+        let mut flashblock: Flashblock =
+            serde_json::from_value(self.json).expect("test flashblock is valid");
+        flashblock.received = self.received;
+        flashblock
+    }
+}
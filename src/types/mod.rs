@@ -1,5 +1,7 @@
+mod borrowed;
 mod flashblock;
 mod rpc;
 
+pub use borrowed::*;
 pub use flashblock::*;
 pub use rpc::*;
@@ -25,7 +25,8 @@ pub struct JsonRpcResponse<T> {
     pub jsonrpc: String,
     pub result: Option<T>,
     pub error: Option<JsonRpcError>,
-    pub id: u64,
+    /// `null` in replies to requests whose id could not be read.
+    pub id: Option<u64>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -34,3 +35,21 @@ pub struct JsonRpcError {
     pub message: String,
     pub data: Option<Value>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn error_replies_may_have_a_null_id() {
+        let response: JsonRpcResponse<Value> = serde_json::from_value(json!({
+            "jsonrpc": "2.0",
+            "error": { "code": -32700, "message": "Parse error" },
+            "id": null,
+        }))
+        .unwrap();
+        assert_eq!(response.id, None);
+        assert_eq!(response.error.unwrap().code, -32700);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    state::{transaction_type, PendingBlock},
+    types::{hex_to_u64, parse_log},
+};
+use alloy::{
+    consensus::proofs::ordered_trie_root_with_encoder,
+    primitives::{hex, logs_bloom, B256},
+    rlp::{Encodable, Header},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::str::FromStr;
+
+/// Transaction type of OP Stack deposit transactions.
+const DEPOSIT_TX_TYPE: u8 = 0x7e;
+
+/// Outcome of comparing the roots computed from a pending block with the
+/// roots in the canonical header.
+#[derive(Debug, Clone, Serialize)]
+pub struct RootVerification {
+    pub transactions_root: Option<B256>,
+    pub receipts_root: Option<B256>,
+    pub transactions_root_matches: Option<bool>,
+    pub receipts_root_matches: Option<bool>,
+}
+
+impl RootVerification {
+    pub fn is_valid(&self) -> bool {
+        self.transactions_root_matches != Some(false) && self.receipts_root_matches != Some(false)
+    }
+}
+
+/// Computes both roots of `block` and compares them with the
+/// `transactionsRoot` and `receiptsRoot` of the canonical header. A comparison
+/// is `None` when either side is unavailable.
+pub fn verify_roots(block: &PendingBlock, canonical: &Value) -> RootVerification {
+    let transactions_root = transactions_root(block);
+    let receipts_root = receipts_root(block);
+    let expected = |field: &str| {
+        canonical[field]
+            .as_str()
+            .and_then(|s| B256::from_str(s).ok())
+    };
+
+    RootVerification {
+        transactions_root,
+        receipts_root,
+        transactions_root_matches: transactions_root
+            .zip(expected("transactionsRoot"))
+            .map(|(a, b)| a == b),
+        receipts_root_matches: receipts_root
+            .zip(expected("receiptsRoot"))
+            .map(|(a, b)| a == b),
+    }
+}
+
+/// Transactions trie root of the raw EIP-2718 encoded transactions.
+pub fn transactions_root(block: &PendingBlock) -> Option<B256> {
+    let raw = block
+        .transactions
+        .iter()
+        .map(hex::decode)
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    Some(ordered_trie_root_with_encoder(&raw, |tx, buf| {
+        buf.extend_from_slice(tx)
+    }))
+}
+
+/// Receipts trie root. Returns `None` if any transaction lacks a receipt.
+pub fn receipts_root(block: &PendingBlock) -> Option<B256> {
+    let encoded = block
+        .transactions
+        .iter()
+        .zip(&block.transaction_hashes)
+        .map(|(raw, hash)| encode_receipt(transaction_type(raw)?, block.receipt(hash)?))
+        .collect::<Option<Vec<_>>>()?;
+    Some(ordered_trie_root_with_encoder(&encoded, |receipt, buf| {
+        buf.extend_from_slice(receipt)
+    }))
+}
+
+/// Encodes a receipt as it is stored in the receipts trie, including the
+/// deposit nonce and receipt version of post-Canyon deposit receipts.
+fn encode_receipt(tx_type: u8, receipt: &Value) -> Option<Vec<u8>> {
+    let status = receipt["status"].as_str().and_then(hex_to_u64)? == 1;
+    let cumulative_gas_used = receipt["cumulativeGasUsed"].as_str().and_then(hex_to_u64)?;
+    let logs = receipt["logs"]
+        .as_array()
+        .map(|logs| logs.iter().map(parse_log).collect::<Option<Vec<_>>>())
+        .unwrap_or(Some(Vec::new()))?;
+    let bloom = logs_bloom(&logs);
+
+    let (deposit_nonce, deposit_receipt_version) = if tx_type == DEPOSIT_TX_TYPE {
+        (
+            receipt["depositNonce"].as_str().and_then(hex_to_u64),
+            receipt["depositReceiptVersion"]
+                .as_str()
+                .and_then(hex_to_u64),
+        )
+    } else {
+        (None, None)
+    };
+
+    let payload_length = status.length()
+        + cumulative_gas_used.length()
+        + bloom.length()
+        + logs.length()
+        + deposit_nonce.map_or(0, |n| n.length())
+        + deposit_receipt_version.map_or(0, |v| v.length());
+
+    let mut out = Vec::new();
+    if tx_type != 0 {
+        out.push(tx_type);
+    }
+    Header {
+        list: true,
+        payload_length,
+    }
+    .encode(&mut out);
+    status.encode(&mut out);
+    cumulative_gas_used.encode(&mut out);
+    bloom.encode(&mut out);
+    logs.encode(&mut out);
+    if let Some(nonce) = deposit_nonce {
+        nonce.encode(&mut out);
+    }
+    if let Some(version) = deposit_receipt_version {
+        version.encode(&mut out);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::transaction_hash;
+    use alloy::{
+        consensus::{Eip658Value, Receipt, ReceiptEnvelope, ReceiptWithBloom},
+        eips::eip2718::Encodable2718,
+        primitives::{Address, Bytes, Log},
+    };
+    use op_alloy_consensus::{OpDepositReceipt, OpReceiptEnvelope};
+    use serde_json::{json, Map};
+
+    fn log() -> Log {
+        Log::new_unchecked(
+            Address::repeat_byte(0x42),
+            vec![B256::repeat_byte(0x01), B256::repeat_byte(0x02)],
+            Bytes::from_static(&[0xde, 0xad]),
+        )
+    }
+
+    fn receipt() -> Receipt {
+        Receipt {
+            status: Eip658Value::Eip658(true),
+            cumulative_gas_used: 42_000,
+            logs: vec![log()],
+        }
+    }
+
+    fn receipt_json(extra: Value) -> Value {
+        let mut receipt = json!({
+            "status": "0x1",
+            "cumulativeGasUsed": "0xa410",
+            "logs": [{
+                "address": Address::repeat_byte(0x42),
+                "topics": [B256::repeat_byte(0x01), B256::repeat_byte(0x02)],
+                "data": "0xdead",
+            }],
+        });
+        if let (Some(receipt), Some(extra)) = (receipt.as_object_mut(), extra.as_object()) {
+            receipt.extend(extra.clone());
+        }
+        receipt
+    }
+
+    fn deposit_receipt() -> OpReceiptEnvelope {
+        OpReceiptEnvelope::Deposit(ReceiptWithBloom::from(OpDepositReceipt {
+            inner: receipt(),
+            deposit_nonce: Some(7),
+            deposit_receipt_version: Some(1),
+        }))
+    }
+
+    #[test]
+    fn encodes_signed_transaction_receipts() {
+        let json = receipt_json(json!({}));
+        let legacy = ReceiptEnvelope::Legacy(ReceiptWithBloom::from(receipt()));
+        let eip1559 = ReceiptEnvelope::Eip1559(ReceiptWithBloom::from(receipt()));
+
+        assert_eq!(encode_receipt(0, &json), Some(legacy.encoded_2718()));
+        assert_eq!(encode_receipt(2, &json), Some(eip1559.encoded_2718()));
+    }
+
+    #[test]
+    fn encodes_deposit_receipts() {
+        let json = receipt_json(json!({ "depositNonce": "0x7", "depositReceiptVersion": "0x1" }));
+        assert_eq!(
+            encode_receipt(DEPOSIT_TX_TYPE, &json),
+            Some(deposit_receipt().encoded_2718())
+        );
+    }
+
+    #[test]
+    fn rejects_incomplete_receipts() {
+        assert_eq!(
+            encode_receipt(2, &json!({ "cumulativeGasUsed": "0x0" })),
+            None
+        );
+        assert_eq!(encode_receipt(2, &json!({ "status": "0x1" })), None);
+    }
+
+    #[test]
+    fn computes_receipts_root_in_transaction_order() {
+        let transactions = vec!["0x7e01".to_string(), "0x0202".to_string()];
+        let hashes: Vec<String> = transactions
+            .iter()
+            .map(|raw| transaction_hash(raw).unwrap())
+            .collect();
+        let mut block = PendingBlock {
+            payload_id: "0x01".to_string(),
+            block_number: 1,
+            base: None,
+            index: 0,
+            transactions,
+            transaction_hashes: hashes.clone(),
+            receipts: Map::new(),
+            balances: Map::new(),
+            state_root: None,
+            block_hash: None,
+            gas_used: None,
+        };
+        block.receipts.insert(
+            hashes[0].clone(),
+            json!({ "Deposit": receipt_json(json!({ "depositNonce": "0x7", "depositReceiptVersion": "0x1" })) }),
+        );
+        assert_eq!(receipts_root(&block), None);
+
+        block.receipts.insert(
+            hashes[1].clone(),
+            json!({ "Eip1559": receipt_json(json!({})) }),
+        );
+        let expected = [
+            deposit_receipt().encoded_2718(),
+            OpReceiptEnvelope::Eip1559(ReceiptWithBloom::from(receipt())).encoded_2718(),
+        ];
+        assert_eq!(
+            receipts_root(&block),
+            Some(ordered_trie_root_with_encoder(&expected, |receipt, buf| {
+                buf.extend_from_slice(receipt)
+            }))
+        );
+    }
+}
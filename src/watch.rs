@@ -0,0 +1,276 @@
+use crate::{
+    server::decode_transaction,
+    state::transaction_hash,
+    types::{unwrap_receipt, Flashblock},
+};
+use alloy::{
+    consensus::Transaction,
+    primitives::{utils::format_ether, Address, B256, I256, U256},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{collections::HashMap, str::FromStr};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+/// A change of a watched address's balance within a flashblock.
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceChange {
+    pub address: Address,
+    pub block_number: Option<u64>,
+    pub payload_id: String,
+    pub index: u64,
+    /// `None` the first time the address is seen without a seeded balance
+    pub old_balance: Option<U256>,
+    pub new_balance: U256,
+    pub delta: Option<I256>,
+    /// Transactions in the flashblock sent by, sent to, or emitting a log
+    /// that references the address
+    pub transactions: Vec<String>,
+}
+
+impl BalanceChange {
+    pub fn old_balance_ether(&self) -> Option<String> {
+        self.old_balance.map(format_ether)
+    }
+
+    pub fn new_balance_ether(&self) -> String {
+        format_ether(self.new_balance)
+    }
+
+    pub fn delta_ether(&self) -> Option<String> {
+        self.delta.map(|delta| {
+            let sign = if delta.is_negative() { "-" } else { "+" };
+            format!("{}{}", sign, format_ether(delta.unsigned_abs()))
+        })
+    }
+}
+
+/// Tracks the balances of a set of addresses across flashblocks.
+#[derive(Debug, Clone, Default)]
+pub struct Watchlist {
+    balances: HashMap<Address, Option<U256>>,
+}
+
+impl Watchlist {
+    pub fn new(addresses: impl IntoIterator<Item = Address>) -> Self {
+        Self {
+            balances: addresses.into_iter().map(|a| (a, None)).collect(),
+        }
+    }
+
+    pub fn watch(&mut self, address: Address) {
+        self.balances.entry(address).or_insert(None);
+    }
+
+    pub fn unwatch(&mut self, address: &Address) {
+        self.balances.remove(address);
+    }
+
+    /// Sets the known balance of a watched address so that its first change
+    /// reports an old value and delta.
+    pub fn seed(&mut self, address: Address, balance: U256) {
+        self.balances.insert(address, Some(balance));
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = &Address> {
+        self.balances.keys()
+    }
+
+    pub fn balance(&self, address: &Address) -> Option<U256> {
+        self.balances.get(address).copied().flatten()
+    }
+
+    /// Returns a change for every watched address whose balance in the
+    /// flashblock's `new_account_balances` differs from the last known one.
+    pub fn observe(&mut self, flashblock: &Flashblock) -> Vec<BalanceChange> {
+        let Some(balances) = flashblock
+            .metadata
+            .new_account_balances
+            .as_ref()
+            .and_then(Value::as_object)
+        else {
+            return Vec::new();
+        };
+
+        let mut changes = Vec::new();
+        for (address, balance) in balances {
+            let (Ok(address), Some(Ok(new_balance))) = (
+                Address::from_str(address),
+                balance.as_str().map(U256::from_str),
+            ) else {
+                continue;
+            };
+            let Some(known) = self.balances.get_mut(&address) else {
+                continue;
+            };
+            if *known == Some(new_balance) {
+                continue;
+            }
+
+            let old_balance = known.replace(new_balance);
+            let delta = old_balance
+                .map(|old| I256::from_raw(new_balance).wrapping_sub(I256::from_raw(old)));
+            debug!("Balance of {} changed to {}", address, new_balance);
+
+            changes.push(BalanceChange {
+                address,
+                block_number: flashblock.block_number(),
+                payload_id: flashblock.payload_id.clone(),
+                index: flashblock.index,
+                old_balance,
+                new_balance,
+                delta,
+                transactions: triggering_transactions(flashblock, address),
+            });
+        }
+        changes
+    }
+
+    /// Consumes flashblocks and sends every balance change of a watched
+    /// address.
+    pub async fn run(
+        mut self,
+        mut flashblocks: mpsc::UnboundedReceiver<Flashblock>,
+        changes: mpsc::UnboundedSender<BalanceChange>,
+    ) {
+        while let Some(flashblock) = flashblocks.recv().await {
+            for change in self.observe(&flashblock) {
+                if changes.send(change).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Hashes of the transactions in the flashblock that plausibly moved the
+/// address's balance: sent by or to it, or emitting a log with the address
+/// as a topic (e.g. an ERC-20 or WETH transfer).
+fn triggering_transactions(flashblock: &Flashblock, address: Address) -> Vec<String> {
+    let topic = B256::left_padding_from(address.as_slice());
+
+    flashblock
+        .diff
+        .transactions
+        .iter()
+        .flatten()
+        .filter_map(|raw| {
+            let hash = transaction_hash(raw)?;
+            let direct = decode_transaction(raw).is_some_and(|tx| {
+                tx.to() == Some(address) || tx.recover_signer().ok() == Some(address)
+            });
+            let logged = flashblock
+                .metadata
+                .receipt(&hash)
+                .and_then(|receipt| unwrap_receipt(receipt)["logs"].as_array())
+                .is_some_and(|logs| logs.iter().any(|log| log_mentions(log, address, topic)));
+            (direct || logged).then_some(hash)
+        })
+        .collect()
+}
+
+fn log_mentions(log: &Value, address: Address, topic: B256) -> bool {
+    let emitted_by = log["address"]
+        .as_str()
+        .and_then(|a| Address::from_str(a).ok())
+        == Some(address);
+    emitted_by
+        || log["topics"].as_array().is_some_and(|topics| {
+            topics
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|t| B256::from_str(t).ok() == Some(topic))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FlashblockBuilder;
+    use serde_json::json;
+
+    const WATCHED: Address = Address::repeat_byte(0xaa);
+    const OTHER: Address = Address::repeat_byte(0xbb);
+
+    fn flashblock(index: u64) -> FlashblockBuilder {
+        Flashblock::builder("0x01", index)
+            .with_transactions(["0x01", "0x02"])
+            .with_block_number(100)
+    }
+
+    fn wei(value: u64) -> String {
+        format!("{:#x}", value)
+    }
+
+    #[test]
+    fn reports_deltas_from_seeded_balances() {
+        let mut watchlist = Watchlist::new([WATCHED]);
+        watchlist.seed(WATCHED, U256::from(1_000));
+
+        let changes = watchlist.observe(
+            &flashblock(0)
+                .with_balance(&WATCHED.to_string(), wei(400))
+                .with_balance(&OTHER.to_string(), wei(5))
+                .build(),
+        );
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].address, WATCHED);
+        assert_eq!(changes[0].old_balance, Some(U256::from(1_000)));
+        assert_eq!(changes[0].new_balance, U256::from(400));
+        assert_eq!(changes[0].delta, Some(I256::try_from(-600).unwrap()));
+        assert_eq!(changes[0].delta_ether().unwrap(), "-0.000000000000000600");
+        assert_eq!(watchlist.balance(&WATCHED), Some(U256::from(400)));
+    }
+
+    #[test]
+    fn first_sighting_has_no_delta_and_repeats_are_skipped() {
+        let mut watchlist = Watchlist::new([WATCHED]);
+        let balance = |index| {
+            flashblock(index)
+                .with_balance(&WATCHED.to_string().to_lowercase(), wei(7))
+                .build()
+        };
+
+        let first = watchlist.observe(&balance(0));
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].old_balance, None);
+        assert_eq!(first[0].delta, None);
+
+        assert!(watchlist.observe(&balance(1)).is_empty());
+
+        watchlist.unwatch(&WATCHED);
+        assert!(watchlist
+            .observe(
+                &flashblock(2)
+                    .with_balance(&WATCHED.to_string(), wei(8))
+                    .build()
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn attributes_changes_to_transactions_logging_the_address() {
+        let mut watchlist = Watchlist::new([WATCHED]);
+        let logging = transaction_hash("0x02").unwrap();
+        let topic = B256::left_padding_from(WATCHED.as_slice());
+        let log = |topics: Vec<B256>| json!({ "logs": [{ "address": OTHER, "topics": topics, "data": "0x" }] });
+
+        let changes = watchlist.observe(
+            &flashblock(0)
+                .with_balance(&WATCHED.to_string(), wei(1))
+                .with_receipt(
+                    &logging.to_uppercase().replace("0X", "0x"),
+                    "Eip1559",
+                    log(vec![B256::ZERO, topic]),
+                )
+                .with_receipt(
+                    &transaction_hash("0x01").unwrap(),
+                    "Eip1559",
+                    log(vec![B256::ZERO]),
+                )
+                .build(),
+        );
+        assert_eq!(changes[0].transactions, vec![logging]);
+    }
+}
@@ -1,19 +1,165 @@
-use crate::types::Flashblock;
+use crate::{
+    decode::{decode_flashblock, decode_notification, subscription_id, DecodeError},
+    handler::{FlashblockHandler, LoggingHandler},
+    types::{Flashblock, ReceiveTime},
+};
 use eyre::{Result, WrapErr};
 use futures_util::{SinkExt, StreamExt};
-use std::time::Duration;
-use tokio::time;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{str::FromStr, sync::Arc, time::Duration};
+use tokio::{
+    sync::{mpsc, watch},
+    time,
+};
 use tokio_tungstenite::{
     connect_async,
-    tungstenite::{protocol::Message, Error as WsError},
+    tungstenite::{
+        protocol::{frame::coding::CloseCode, CloseFrame, Message},
+        Bytes, Error as WsError,
+    },
 };
-use tracing::{debug, error, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 use url::Url;
 
+/// Connection state of a [`FlashblocksWsClient`], published through
+/// [`FlashblocksWsClient::health`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthStatus {
+    Connecting,
+    Connected,
+    /// No flashblock arrived within the idle timeout; a reconnect follows.
+    Stalled,
+    Reconnecting,
+    /// The client stopped, either done or out of attempts.
+    Disconnected,
+}
+
+impl HealthStatus {
+    /// Whether flashblocks are flowing, for readiness probes.
+    pub fn is_ready(&self) -> bool {
+        *self == HealthStatus::Connected
+    }
+}
+
+/// The handshake sent after connecting, which selects the feed's protocol.
+///
+/// In a configuration file this is a table with a `type` of `none`,
+/// `json_rpc` (with `method` and `params`) or `eth_subscribe` (with `name`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Subscription {
+    /// Send nothing; the server streams flashblocks as soon as it accepts
+    /// the connection.
+    None,
+    /// A JSON-RPC request with an arbitrary method and params.
+    JsonRpc {
+        method: String,
+        #[serde(default)]
+        params: Value,
+    },
+    /// `eth_subscribe` with the given subscription name. Flashblocks arrive
+    /// as notifications wrapped in `params.result`.
+    EthSubscribe {
+        #[serde(default = "default_subscription_name")]
+        name: String,
+    },
+}
+
+fn default_subscription_name() -> String {
+    "newFlashblocks".to_string()
+}
+
+impl Default for Subscription {
+    fn default() -> Self {
+        Subscription::JsonRpc {
+            method: "subscribe".to_string(),
+            params: json!(["flashblocks"]),
+        }
+    }
+}
+
+impl Subscription {
+    pub fn eth_subscribe() -> Self {
+        Subscription::EthSubscribe {
+            name: default_subscription_name(),
+        }
+    }
+
+    /// The request to send after connecting, if any.
+    pub fn request(&self, id: u64) -> Option<Value> {
+        let (method, params) = match self {
+            Subscription::None => return None,
+            Subscription::JsonRpc { method, params } => (method.as_str(), params.clone()),
+            Subscription::EthSubscribe { name } => ("eth_subscribe", json!([name])),
+        };
+        let mut request = json!({ "jsonrpc": "2.0", "method": method, "id": id });
+        if !params.is_null() {
+            request["params"] = params;
+        }
+        Some(request)
+    }
+
+    /// Decodes a message of this feed, trying the notification envelope
+    /// first for `eth_subscribe` feeds.
+    pub fn decode(&self, message: &mut [u8]) -> Result<Option<Flashblock>> {
+        match self {
+            Subscription::EthSubscribe { .. } => decode_notification(message),
+            _ => decode_flashblock(message),
+        }
+    }
+}
+
+/// Parses `none`, `eth_subscribe`, `eth_subscribe:<name>`, `<method>` or
+/// `<method>:<params as JSON>`.
+impl FromStr for Subscription {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (method, params) = match s.split_once(':') {
+            Some((method, params)) => (method, Some(params)),
+            None => (s, None),
+        };
+        Ok(match (method, params) {
+            ("none", None) => Subscription::None,
+            ("eth_subscribe", None) => Subscription::eth_subscribe(),
+            ("eth_subscribe", Some(name)) => Subscription::EthSubscribe {
+                name: name.to_string(),
+            },
+            (method, params) => Subscription::JsonRpc {
+                method: method.to_string(),
+                params: params
+                    .map(serde_json::from_str)
+                    .transpose()
+                    .wrap_err("Subscription params are not valid JSON")?
+                    .unwrap_or(Value::Null),
+            },
+        })
+    }
+}
+
+enum SessionEnd {
+    /// Cancelled or done with the requested blocks.
+    Finished,
+    /// The connection stalled or was lost; `received` tells whether any
+    /// flashblock arrived before.
+    Lost { received: bool },
+}
+
 pub struct FlashblocksWsClient {
     url: Url,
     max_blocks: usize,
+    max_attempts: u32,
     reconnect_delay: Duration,
+    connect_timeout: Duration,
+    ping_interval: Duration,
+    idle_timeout: Duration,
+    subscription: Subscription,
+    health: watch::Sender<HealthStatus>,
+    cancel: CancellationToken,
+    handlers: Vec<Arc<dyn FlashblockHandler>>,
+    bounded_sender: Option<mpsc::Sender<Flashblock>>,
 }
 
 impl FlashblocksWsClient {
@@ -21,7 +167,16 @@ impl FlashblocksWsClient {
         Self {
             url,
             max_blocks,
+            max_attempts: 3,
             reconnect_delay: Duration::from_secs(1),
+            connect_timeout: Duration::from_secs(30),
+            ping_interval: Duration::from_secs(15),
+            idle_timeout: Duration::from_secs(10),
+            subscription: Subscription::default(),
+            health: watch::channel(HealthStatus::Connecting).0,
+            cancel: CancellationToken::new(),
+            handlers: vec![Arc::new(LoggingHandler::default())],
+            bounded_sender: None,
         }
     }
 
@@ -30,28 +185,127 @@ impl FlashblocksWsClient {
         self
     }
 
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Interval at which pings are sent to keep the connection alive.
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Reconnects when no flashblock has arrived for this long, which catches
+    /// half-open connections that never report an error.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// The handshake sent after each connect. Defaults to
+    /// `subscribe(["flashblocks"])`.
+    pub fn with_subscription(mut self, subscription: Subscription) -> Self {
+        self.subscription = subscription;
+        self
+    }
+
+    /// Stops streaming once `cancel` is cancelled, closing the connection
+    /// with a close frame.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The token that stops this client, e.g. to derive a child token that
+    /// stops it early without affecting others.
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Watches the connection state, e.g. to answer readiness probes.
+    pub fn health(&self) -> watch::Receiver<HealthStatus> {
+        self.health.subscribe()
+    }
+
+    pub fn with_sender(self, sender: mpsc::UnboundedSender<Flashblock>) -> Self {
+        self.with_handler(sender)
+    }
+
+    /// Sends flashblocks to a bounded channel after the handlers have run.
+    /// The socket is not read while the channel is full.
+    pub fn with_bounded_sender(mut self, sender: mpsc::Sender<Flashblock>) -> Self {
+        self.bounded_sender = Some(sender);
+        self
+    }
+
+    /// Registers a handler alongside those already registered. Handlers are
+    /// called in registration order.
+    pub fn with_handler(mut self, handler: impl FlashblockHandler + 'static) -> Self {
+        self.handlers.push(Arc::new(handler));
+        self
+    }
+
+    /// Replaces every registered handler, including the default
+    /// [`LoggingHandler`].
+    pub fn with_handlers(mut self, handlers: Vec<Arc<dyn FlashblockHandler>>) -> Self {
+        self.handlers = handlers;
+        self
+    }
+
     pub async fn stream_blocks(&self) -> Result<()> {
         info!("Connecting to Flashblocks WebSocket at {}", self.url);
 
         let mut attempts = 0;
-        let max_attempts = 3;
+        let max_attempts = self.max_attempts;
+        // Counted across reconnects so that a lost connection does not
+        // restart the count.
+        let mut block_count = 0;
 
-        while attempts < max_attempts {
-            match self.connect_and_stream().await {
-                Ok(_) => break,
+        while attempts < max_attempts && !self.cancel.is_cancelled() {
+            match self.connect_and_stream(&mut block_count).await {
+                Ok(SessionEnd::Finished) => break,
+                Ok(SessionEnd::Lost { received }) => {
+                    // Only sessions that delivered nothing count towards the
+                    // limit, so a feed that connects and then always stalls
+                    // still gives up.
+                    if received {
+                        attempts = 0;
+                    } else {
+                        attempts += 1;
+                        warn!(
+                            "Connection lost before any flashblock arrived (attempt {}/{})",
+                            attempts, max_attempts
+                        );
+                    }
+                    if attempts < max_attempts {
+                        self.reconnect(attempts).await;
+                    }
+                }
                 Err(e) => {
                     attempts += 1;
-                    error!(
-                        "WebSocket error (attempt {}/{}): {}",
-                        attempts, max_attempts, e
-                    );
+                    let e = e.wrap_err(format!(
+                        "WebSocket error (attempt {}/{})",
+                        attempts, max_attempts
+                    ));
+                    self.on_error(e);
                     if attempts < max_attempts {
-                        time::sleep(self.reconnect_delay).await;
+                        self.reconnect(attempts).await;
                     }
                 }
             }
         }
 
+        self.health.send_replace(HealthStatus::Disconnected);
         if attempts == max_attempts {
             error!("Failed to connect after {} attempts", max_attempts);
             return Err(eyre::eyre!("Max connection attempts reached"));
@@ -60,175 +314,332 @@ impl FlashblocksWsClient {
         Ok(())
     }
 
-    async fn connect_and_stream(&self) -> Result<()> {
-        let (mut ws_stream, _) = connect_async(self.url.as_str())
-            .await
-            .wrap_err("Failed to establish WebSocket connection")?;
+    async fn reconnect(&self, attempts: u32) {
+        if self.cancel.is_cancelled() {
+            return;
+        }
+        self.health.send_replace(HealthStatus::Reconnecting);
+        self.handlers.iter().for_each(|h| h.on_reconnect(attempts));
+        tokio::select! {
+            _ = time::sleep(self.reconnect_delay) => {}
+            _ = self.cancel.cancelled() => {}
+        }
+    }
+
+    async fn connect_and_stream(&self, block_count: &mut usize) -> Result<SessionEnd> {
+        let connect = time::timeout(self.connect_timeout, connect_async(self.url.as_str()));
+        let (mut ws_stream, _) = tokio::select! {
+            connected = connect => connected
+                .wrap_err("Timed out establishing WebSocket connection")?
+                .wrap_err("Failed to establish WebSocket connection")?,
+            _ = self.cancel.cancelled() => return Ok(SessionEnd::Finished),
+        };
         info!("WebSocket connection established");
+        self.health.send_replace(HealthStatus::Connected);
 
-        let init_msg = serde_json::json!({
-            "jsonrpc": "2.0",
-            "method": "subscribe",
-            "params": ["flashblocks"],
-            "id": 1
-        });
-        let init_str = init_msg.to_string();
-        ws_stream
-            .send(Message::Text(init_str.as_str().into()))
-            .await
-            .wrap_err("Failed to send subscription request")?;
-        info!("Sent subscription request");
+        // Set until the subscribe response or the first flashblock arrives,
+        // so that later messages are not checked for an acknowledgement.
+        let mut awaiting_ack = false;
+        if let Some(request) = self.subscription.request(1) {
+            ws_stream
+                .send(Message::Text(request.to_string().into()))
+                .await
+                .wrap_err("Failed to send subscription request")?;
+            info!("Sent subscription request");
+            awaiting_ack = true;
+        }
 
-        let mut block_count = 0;
+        let mut current: Option<(String, u64)> = None;
         info!("Awaiting Flashblocks... hi");
 
-        while let Some(msg) = ws_stream.next().await {
-            match msg {
-                Ok(Message::Text(text)) => match serde_json::from_str::<serde_json::Value>(&text) {
-                    Ok(json) => {
-                        if let Some(error) = json.get("error") {
-                            error!("Received JSON-RPC error: {}", error);
-                            continue;
-                        }
-
-                        match serde_json::from_value::<Flashblock>(json.clone()) {
-                            Ok(flashblock) => {
-                                self.handle_flashblock(&flashblock, &mut block_count)
-                                    .await?;
-                                if block_count >= self.max_blocks && flashblock.is_initial() {
-                                    info!(
-                                        "\nReached maximum block count ({}), exiting",
-                                        self.max_blocks
-                                    );
-                                    break;
-                                }
-                            }
-                            Err(e) => {
-                                debug!("Not a Flashblock message: {}", e);
-                                debug!("Raw message: {}", text);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to parse message as JSON: {}", e);
-                        error!("Raw message: {}", text);
-                    }
-                },
-                Ok(Message::Binary(data)) => match String::from_utf8(data.to_vec()) {
-                    Ok(text) => {
-                        debug!("Received binary message: {}", text);
-                        match serde_json::from_str::<serde_json::Value>(&text) {
-                            Ok(json) => {
-                                if let Ok(flashblock) =
-                                    serde_json::from_value::<Flashblock>(json.clone())
-                                {
-                                    self.handle_flashblock(&flashblock, &mut block_count)
-                                        .await?;
-                                    if block_count >= self.max_blocks && flashblock.is_initial() {
-                                        info!(
-                                            "\nReached maximum block count ({}), exiting",
-                                            self.max_blocks
-                                        );
-                                        break;
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                error!("Failed to parse binary message as JSON: {}", e);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to decode binary message as UTF-8: {}", e);
+        let mut ping = time::interval_at(
+            time::Instant::now() + self.ping_interval,
+            self.ping_interval,
+        );
+        let idle = time::sleep(self.idle_timeout);
+        tokio::pin!(idle);
+        let mut received_any = false;
+        let mut finished = false;
+
+        let reason = loop {
+            let msg = tokio::select! {
+                msg = ws_stream.next() => msg,
+                _ = ping.tick() => {
+                    ws_stream
+                        .send(Message::Ping(Bytes::new()))
+                        .await
+                        .wrap_err("Failed to send ping")?;
+                    continue;
+                }
+                _ = self.cancel.cancelled() => {
+                    let frame = CloseFrame {
+                        code: CloseCode::Normal,
+                        reason: "client shutdown".into(),
+                    };
+                    if let Err(e) = ws_stream.close(Some(frame)).await {
+                        debug!("Failed to send close frame: {}", e);
                     }
-                },
+                    // Wait briefly for the server to acknowledge the close.
+                    let _ = time::timeout(Duration::from_secs(1), async {
+                        while let Some(Ok(_)) = ws_stream.next().await {}
+                    })
+                    .await;
+                    finished = true;
+                    break "cancelled".to_string();
+                }
+                _ = &mut idle => {
+                    self.health.send_replace(HealthStatus::Stalled);
+                    break format!("no flashblock received for {:?}", self.idle_timeout);
+                }
+            };
+            let Some(msg) = msg else {
+                break "stream ended".to_string();
+            };
+            let received = ReceiveTime::now();
+            let mut message = match msg {
+                Ok(Message::Text(text)) => Vec::from(Bytes::from(text)),
+                Ok(Message::Binary(data)) => {
+                    debug!(
+                        "Received binary message: {}",
+                        String::from_utf8_lossy(&data)
+                    );
+                    Vec::from(data)
+                }
                 Ok(Message::Ping(data)) => {
                     ws_stream
                         .send(Message::Pong(data))
                         .await
                         .wrap_err("Failed to respond to ping")?;
+                    continue;
                 }
-                Ok(Message::Pong(_)) => {}
-                Ok(Message::Close(frame)) => {
-                    info!("WebSocket connection closed by server: {:?}", frame);
-                    break;
+                Ok(Message::Pong(_)) | Ok(Message::Frame(_)) => continue,
+                Ok(Message::Close(frame)) => break format!("closed by server: {:?}", frame),
+                Err(WsError::ConnectionClosed) => break "connection closed".to_string(),
+                Err(WsError::Protocol(p)) => break format!("protocol error: {}", p),
+                Err(e) => break format!("WebSocket error: {}", e),
+            };
+
+            if awaiting_ack {
+                if let Some(id) = subscription_id(&message) {
+                    info!("Subscribed with id {}", id);
+                    awaiting_ack = false;
+                    continue;
                 }
-                Ok(Message::Frame(_)) => {}
-                Err(e) => match e {
-                    WsError::Protocol(p) => {
-                        error!("WebSocket protocol error: {}", p);
-                        break;
-                    }
-                    WsError::ConnectionClosed => {
-                        info!("WebSocket connection closed");
-                        break;
-                    }
-                    _ => {
-                        error!("WebSocket error: {}", e);
-                        break;
-                    }
-                },
             }
-        }
-
-        Ok(())
-    }
+            let mut flashblock = match self.subscription.decode(&mut message) {
+                Ok(Some(flashblock)) => flashblock,
+                Ok(None) => continue,
+                Err(e) => {
+                    self.on_error(e.wrap_err(DecodeError));
+                    continue;
+                }
+            };
+            awaiting_ack = false;
+            received_any = true;
+            flashblock.received = Some(received);
+            self.dispatch(&flashblock, &mut current);
 
-    async fn handle_flashblock(
-        &self,
-        flashblock: &Flashblock,
-        block_count: &mut usize,
-    ) -> Result<()> {
-        if flashblock.is_initial() {
-            *block_count += 1;
-            info!("\nNew block started (#{}/{})", block_count, self.max_blocks);
-            info!("Payload ID: {}", flashblock.payload_id);
+            let is_initial = flashblock.is_initial();
+            if let Some(sender) = &self.bounded_sender {
+                if sender.send(flashblock).await.is_err() {
+                    debug!("Flashblock receiver dropped");
+                }
+            }
+            // Reset after a possibly blocking send so that time spent waiting
+            // on a slow consumer does not count as a stall.
+            idle.as_mut()
+                .reset(time::Instant::now() + self.idle_timeout);
 
-            if let Some(base) = &flashblock.base {
-                if let Some(number) = flashblock.block_number() {
-                    info!("Block number: {}", number);
+            if is_initial {
+                *block_count += 1;
+                if *block_count >= self.max_blocks {
+                    info!(
+                        "\nReached maximum block count ({}), exiting",
+                        self.max_blocks
+                    );
+                    finished = true;
+                    break "reached maximum block count".to_string();
                 }
-                info!("Parent hash: {}", base.parent_hash);
-                info!("Gas limit: {}", base.gas_limit);
-                info!("Base fee: {} wei", base.base_fee_per_gas);
             }
+        };
+
+        // The payload may continue on the next connection, but nothing more
+        // of it arrives on this one.
+        if let Some((payload_id, last_index)) = current {
+            self.handlers
+                .iter()
+                .for_each(|h| h.on_block_complete(&payload_id, last_index));
+        }
+        self.handlers.iter().for_each(|h| h.on_disconnect(&reason));
+        Ok(if finished {
+            SessionEnd::Finished
         } else {
-            info!(
-                "\nDiff update #{} for payload {}",
-                flashblock.index, flashblock.payload_id
-            );
-
-            let tx_count = flashblock.transaction_count();
-            if tx_count > 0 {
-                info!("New transactions: {}", tx_count);
+            SessionEnd::Lost {
+                received: received_any,
             }
+        })
+    }
 
-            if let Some(gas_used) = &flashblock.diff.gas_used {
-                info!("Gas used: {}", gas_used);
+    /// Calls the handlers for a flashblock, first completing the previous
+    /// payload when this one starts a new block.
+    fn dispatch(&self, flashblock: &Flashblock, current: &mut Option<(String, u64)>) {
+        match current {
+            Some((payload_id, last_index)) if *payload_id == flashblock.payload_id => {
+                *last_index = flashblock.index;
             }
-
-            if let Some(block_hash) = &flashblock.diff.block_hash {
-                info!("Block hash: {}", block_hash);
+            _ => {
+                if let Some((payload_id, last_index)) = current.take() {
+                    self.handlers
+                        .iter()
+                        .for_each(|h| h.on_block_complete(&payload_id, last_index));
+                }
+                *current = Some((flashblock.payload_id.clone(), flashblock.index));
             }
         }
 
-        self.log_metadata(flashblock).await;
-        Ok(())
+        if let Some(base) = &flashblock.base {
+            self.handlers
+                .iter()
+                .for_each(|h| h.on_base(flashblock, base));
+        }
+        self.handlers.iter().for_each(|h| h.on_diff(flashblock));
+    }
+
+    fn on_error(&self, error: eyre::Report) {
+        self.handlers.iter().for_each(|h| h.on_error(&error));
     }
+}
 
-    async fn log_metadata(&self, flashblock: &Flashblock) {
-        if let Some(balances) = &flashblock.metadata.new_account_balances {
-            let balance_count = balances.as_object().map_or(0, |obj| obj.len());
-            if balance_count > 0 {
-                info!("Updated balances for {} accounts", balance_count);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_subscriptions() {
+        assert_eq!("none".parse::<Subscription>().unwrap(), Subscription::None);
+        assert_eq!(
+            "eth_subscribe".parse::<Subscription>().unwrap(),
+            Subscription::eth_subscribe()
+        );
+        assert_eq!(
+            "eth_subscribe:flashblocks".parse::<Subscription>().unwrap(),
+            Subscription::EthSubscribe {
+                name: "flashblocks".to_string()
+            }
+        );
+        assert_eq!(
+            "subscribe:[\"flashblocks\"]"
+                .parse::<Subscription>()
+                .unwrap(),
+            Subscription::default()
+        );
+        assert_eq!(
+            "flashblocks_subscribe".parse::<Subscription>().unwrap(),
+            Subscription::JsonRpc {
+                method: "flashblocks_subscribe".to_string(),
+                params: Value::Null,
             }
+        );
+        assert!("subscribe:[flashblocks".parse::<Subscription>().is_err());
+    }
+
+    #[test]
+    fn builds_subscription_requests() {
+        assert_eq!(Subscription::None.request(1), None);
+        assert_eq!(
+            Subscription::default().request(1),
+            Some(json!({
+                "jsonrpc": "2.0",
+                "method": "subscribe",
+                "params": ["flashblocks"],
+                "id": 1,
+            }))
+        );
+        assert_eq!(
+            Subscription::eth_subscribe().request(2),
+            Some(json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscribe",
+                "params": ["newFlashblocks"],
+                "id": 2,
+            }))
+        );
+        let request = "flashblocks_subscribe"
+            .parse::<Subscription>()
+            .unwrap()
+            .request(3)
+            .unwrap();
+        assert!(request.get("params").is_none());
+    }
+
+    #[derive(Default)]
+    struct Recorder(std::sync::Mutex<Vec<String>>);
+
+    impl FlashblockHandler for Recorder {
+        fn on_diff(&self, flashblock: &Flashblock) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("diff {}", flashblock.index));
         }
 
-        if let Some(receipts) = &flashblock.metadata.receipts {
-            let receipt_count = receipts.as_object().map_or(0, |obj| obj.len());
-            if receipt_count > 0 {
-                info!("New receipts: {}", receipt_count);
-            }
+        fn on_block_complete(&self, payload_id: &str, last_index: u64) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("complete {payload_id} {last_index}"));
         }
+
+        fn on_disconnect(&self, _reason: &str) {
+            self.0.lock().unwrap().push("disconnect".to_string());
+        }
+
+        fn on_reconnect(&self, attempts: u32) {
+            self.0.lock().unwrap().push(format!("reconnect {attempts}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn completes_the_payload_before_disconnecting() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("ws://{}", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                for index in 0..2 {
+                    let mut flashblock = Flashblock::builder("0x01", index);
+                    if index == 0 {
+                        flashblock = flashblock.with_base();
+                    }
+                    let message = Message::Text(flashblock.json().to_string().into());
+                    ws.send(message).await.unwrap();
+                }
+                let _ = ws.close(None).await;
+            }
+        });
+
+        let recorder = Arc::new(Recorder::default());
+        FlashblocksWsClient::new(url, 2)
+            .with_subscription(Subscription::None)
+            .with_reconnect_delay(Duration::from_millis(10))
+            .with_handlers(vec![recorder.clone()])
+            .stream_blocks()
+            .await
+            .unwrap();
+
+        // The second connection stops at the block limit, without a reconnect.
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            [
+                "diff 0",
+                "diff 1",
+                "complete 0x01 1",
+                "disconnect",
+                "reconnect 0",
+                "diff 0",
+                "complete 0x01 0",
+                "disconnect",
+            ]
+        );
     }
 }